@@ -0,0 +1,133 @@
+//! Acknowledgements: someone has seen an alert and is on it.
+//!
+//! An acknowledged incident is no longer repeated nor escalated, until it
+//! resolves or fires again at a higher severity.
+
+use crate::alerts::antispam::NotifyState;
+use crate::configure;
+use crate::errors::Error;
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+const DEFAULT_RUNTIME_PATH: &str = "state/acks.yml";
+
+/// How long an acknowledgement received over HTTP waits for an open incident
+/// to match, in minutes.
+const PENDING_MINUTES: i64 = 60;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Acknowledgement {
+    pub target: String,
+    /// Name of the rule or check that raised the alert.
+    #[serde(alias = "rule")]
+    pub name: String,
+    /// Who is handling the alert.
+    pub by: String,
+    #[serde(default)]
+    pub comment: String,
+    pub at: DateTime<Utc>,
+}
+
+impl Acknowledgement {
+    /// Whether this acknowledges the incident `state` is about. An
+    /// acknowledgement made before the incident was last notified as new or
+    /// escalated is stale.
+    pub fn applies_to(&self, target: &str, name: &str, state: &NotifyState) -> bool {
+        self.target == target
+            && self.name == name
+            && state.firing
+            && state.notified
+            && state.acknowledged.is_none()
+            && state
+                .unacknowledged_since
+                .is_some_and(|since| self.at >= since)
+    }
+
+    /// Whether an acknowledgement that has not matched an open incident yet
+    /// is too old to wait for one any longer.
+    pub fn is_expired(&self, now: DateTime<Utc>) -> bool {
+        now - self.at >= Duration::minutes(PENDING_MINUTES)
+    }
+}
+
+/// The path of the runtime acknowledgements file, `acks_file` in the config.
+pub fn runtime_path() -> Result<String, Error> {
+    let path = configure::fetch::<String>(String::from("acks_file"))?;
+
+    Ok(if path.is_empty() {
+        String::from(DEFAULT_RUNTIME_PATH)
+    } else {
+        path
+    })
+}
+
+/// Acknowledgements from the runtime acknowledgements file, which is read
+/// again on every poll.
+///
+/// The file holds an `acks` list, and missing it means no acknowledgements.
+pub fn runtime(path: &str) -> Result<Vec<Acknowledgement>, Error> {
+    if !Path::new(path).exists() {
+        return Ok(Vec::new());
+    }
+
+    let mut settings = config::Config::default();
+    settings.merge(config::File::with_name(path))?;
+    match settings.get::<Vec<Acknowledgement>>("acks") {
+        Ok(acks) => Ok(acks),
+        Err(config::ConfigError::NotFound(_)) => Ok(Vec::new()),
+        Err(error) => Err(Error::from(error)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_applies_to_current_incident_only() {
+        let now = Utc::now();
+        let state = NotifyState {
+            firing: true,
+            notified: true,
+            unacknowledged_since: Some(now),
+            ..NotifyState::default()
+        };
+        let ack = Acknowledgement {
+            target: String::from("replica"),
+            name: String::from("replication_lag"),
+            by: String::from("dba"),
+            comment: String::new(),
+            at: now + Duration::minutes(1),
+        };
+
+        assert!(ack.applies_to("replica", "replication_lag", &state));
+        assert!(!ack.applies_to("replica", "io_thread_stopped", &state));
+
+        let stale = Acknowledgement {
+            at: now - Duration::minutes(1),
+            ..ack.clone()
+        };
+        assert!(!stale.applies_to("replica", "replication_lag", &state));
+
+        let acknowledged = NotifyState {
+            acknowledged: Some(ack.clone()),
+            ..state
+        };
+        assert!(!ack.applies_to("replica", "replication_lag", &acknowledged));
+    }
+
+    #[test]
+    fn test_pending_ack_expires() {
+        let ack = Acknowledgement {
+            target: String::from("replica"),
+            name: String::from("replication_lag"),
+            by: String::from("dba"),
+            comment: String::new(),
+            at: Utc::now(),
+        };
+
+        assert!(!ack.is_expired(ack.at + Duration::minutes(59)));
+        assert!(ack.is_expired(ack.at + Duration::minutes(60)));
+    }
+}
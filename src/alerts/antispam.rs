@@ -0,0 +1,504 @@
+//! Decides when an alert is worth a notification.
+//!
+//! Every function here is pure: it is given the previous state of one named
+//! alert on one replica, the latest evaluation and the current time, and
+//! returns the next state along with the decision.
+
+use crate::alerts::ack::Acknowledgement;
+use crate::alerts::Severity;
+use crate::configure;
+use crate::errors::Error;
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+
+/// Anti-spam settings, in minutes, as configured under `antispam`.
+#[derive(Debug, Clone, Deserialize)]
+struct AntispamConfig {
+    suppression_window: i64,
+    repeat_interval: Option<i64>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Policy {
+    /// No two notifications of the same alert are sent closer together than
+    /// this, unless its severity escalates.
+    pub suppression_window: Duration,
+    /// How often a still-firing alert is notified again; never if `None`.
+    pub repeat_interval: Option<Duration>,
+}
+
+impl Policy {
+    /// Load the policy from config.
+    ///
+    /// Without an `antispam` section, `antispam_threshold` is used for both
+    /// the suppression window and the repeat interval.
+    pub fn load() -> Result<Policy, Error> {
+        let config = match configure::fetch_section::<AntispamConfig>("antispam")? {
+            Some(config) => config,
+            None => {
+                let threshold = configure::fetch::<String>(String::from("antispam_threshold"))?
+                    .parse::<i64>()
+                    .map_err(|error| {
+                        Error::Internal(format!("Invalid antispam_threshold: {}", error))
+                    })?;
+
+                AntispamConfig {
+                    suppression_window: threshold,
+                    repeat_interval: Some(threshold),
+                }
+            }
+        };
+
+        Ok(Policy {
+            suppression_window: Duration::minutes(config.suppression_window),
+            repeat_interval: config.repeat_interval.map(Duration::minutes),
+        })
+    }
+}
+
+/// The result of evaluating an alert on the latest poll.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Evaluation {
+    Firing(Severity),
+    Clear,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Reason {
+    /// The first notification of an incident.
+    New,
+    /// A reminder that the incident is still ongoing and unacknowledged.
+    Repeat,
+    /// The severity rose above the one last notified.
+    Escalated,
+    /// A notified incident is over.
+    Resolved,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Decision {
+    Notify(Reason),
+    /// Firing, but held back by the policy.
+    Suppress,
+    /// Nothing to notify.
+    Silent,
+}
+
+/// What was notified about one alert, carried from poll to poll.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct NotifyState {
+    pub firing: bool,
+    /// Whether the current incident has been notified at all.
+    pub notified: bool,
+    pub last_notified_at: Option<DateTime<Utc>>,
+    pub last_severity: Option<Severity>,
+    /// Since when the incident has been waiting for an acknowledgement: its
+    /// first notification, or its last escalation in severity.
+    #[serde(default)]
+    pub unacknowledged_since: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub acknowledged: Option<Acknowledgement>,
+    /// The step of the escalation chain reached so far.
+    #[serde(default)]
+    pub escalation_step: usize,
+}
+
+/// Decide whether to notify, given the previous state of an alert, its
+/// latest evaluation and the current time.
+pub fn decide(
+    previous: &NotifyState,
+    evaluation: Evaluation,
+    now: DateTime<Utc>,
+    policy: &Policy,
+) -> (NotifyState, Decision) {
+    let severity = match evaluation {
+        Evaluation::Clear => {
+            // Only an incident that was announced is announced as resolved.
+            let decision = if previous.firing && previous.notified {
+                Decision::Notify(Reason::Resolved)
+            } else {
+                Decision::Silent
+            };
+            let next = NotifyState {
+                firing: false,
+                notified: false,
+                unacknowledged_since: None,
+                acknowledged: None,
+                escalation_step: 0,
+                ..previous.clone()
+            };
+
+            return (next, decision);
+        }
+        Evaluation::Firing(severity) => severity,
+    };
+
+    let since_notified = previous.last_notified_at.map(|at| now - at);
+    let in_window = since_notified.is_some_and(|since| since < policy.suppression_window);
+    let escalated = previous
+        .last_severity
+        .is_some_and(|last_severity| severity > last_severity);
+    let ongoing = previous.firing && previous.notified;
+    let acknowledged = ongoing && previous.acknowledged.is_some();
+
+    let decision = if escalated && (in_window || ongoing) {
+        Decision::Notify(Reason::Escalated)
+    } else if !ongoing {
+        if in_window {
+            Decision::Suppress
+        } else {
+            Decision::Notify(Reason::New)
+        }
+    } else {
+        let repeat_due = match (policy.repeat_interval, since_notified) {
+            (Some(interval), Some(since)) => since >= interval && !in_window,
+            (Some(_), None) => true,
+            (None, _) => false,
+        };
+
+        if repeat_due && !acknowledged {
+            Decision::Notify(Reason::Repeat)
+        } else {
+            Decision::Suppress
+        }
+    };
+
+    let next = match decision {
+        // A new incident, or one that got worse, needs acknowledging afresh.
+        Decision::Notify(Reason::New) | Decision::Notify(Reason::Escalated) => NotifyState {
+            firing: true,
+            notified: true,
+            last_notified_at: Some(now),
+            last_severity: Some(severity),
+            unacknowledged_since: Some(now),
+            acknowledged: None,
+            escalation_step: if ongoing { previous.escalation_step } else { 0 },
+        },
+        Decision::Notify(_) => NotifyState {
+            firing: true,
+            notified: true,
+            last_notified_at: Some(now),
+            last_severity: Some(severity),
+            ..previous.clone()
+        },
+        _ => NotifyState {
+            firing: true,
+            notified: ongoing,
+            ..previous.clone()
+        },
+    };
+
+    (next, decision)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy() -> Policy {
+        Policy {
+            suppression_window: Duration::minutes(5),
+            repeat_interval: Some(Duration::minutes(30)),
+        }
+    }
+
+    fn start() -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339("2020-06-01T00:00:00Z")
+            .unwrap()
+            .with_timezone(&Utc)
+    }
+
+    /// Run a sequence of `(minutes since start, evaluation)` through the
+    /// state machine and collect the decisions.
+    fn run(policy: &Policy, polls: &[(i64, Evaluation)]) -> Vec<Decision> {
+        let mut state = NotifyState::default();
+
+        polls
+            .iter()
+            .map(|(minutes, evaluation)| {
+                let (next, decision) = decide(
+                    &state,
+                    *evaluation,
+                    start() + Duration::minutes(*minutes),
+                    policy,
+                );
+                state = next;
+                decision
+            })
+            .collect()
+    }
+
+    const WARNING: Evaluation = Evaluation::Firing(Severity::Warning);
+    const CRITICAL: Evaluation = Evaluation::Firing(Severity::Critical);
+    const INFO: Evaluation = Evaluation::Firing(Severity::Info);
+
+    #[test]
+    fn test_clear_is_silent() {
+        assert_eq!(
+            vec![Decision::Silent, Decision::Silent],
+            run(&policy(), &[(0, Evaluation::Clear), (1, Evaluation::Clear)])
+        );
+    }
+
+    #[test]
+    fn test_first_firing_notifies() {
+        let (next, decision) = decide(&NotifyState::default(), WARNING, start(), &policy());
+
+        assert_eq!(Decision::Notify(Reason::New), decision);
+        assert_eq!(
+            NotifyState {
+                firing: true,
+                notified: true,
+                last_notified_at: Some(start()),
+                last_severity: Some(Severity::Warning),
+                unacknowledged_since: Some(start()),
+                acknowledged: None,
+                escalation_step: 0,
+            },
+            next
+        );
+    }
+
+    #[test]
+    fn test_ongoing_incident_is_suppressed_until_repeat_interval() {
+        assert_eq!(
+            vec![
+                Decision::Notify(Reason::New),
+                Decision::Suppress,
+                Decision::Suppress,
+                Decision::Notify(Reason::Repeat),
+                Decision::Suppress,
+                Decision::Notify(Reason::Repeat),
+            ],
+            run(
+                &policy(),
+                &[
+                    (0, WARNING),
+                    (5, WARNING),
+                    (29, WARNING),
+                    (30, WARNING),
+                    (45, WARNING),
+                    (60, WARNING),
+                ]
+            )
+        );
+    }
+
+    #[test]
+    fn test_acknowledged_incident_is_not_repeated_until_escalated() {
+        let (state, _) = decide(&NotifyState::default(), WARNING, start(), &policy());
+        let state = NotifyState {
+            acknowledged: Some(Acknowledgement {
+                target: String::from("replica"),
+                name: String::from("replication_lag"),
+                by: String::from("dba"),
+                comment: String::new(),
+                at: start(),
+            }),
+            ..state
+        };
+
+        let later = start() + Duration::minutes(60);
+        let (state, decision) = decide(&state, WARNING, later, &policy());
+        assert_eq!(Decision::Suppress, decision);
+        assert!(state.acknowledged.is_some());
+
+        let (state, decision) = decide(&state, CRITICAL, later, &policy());
+        assert_eq!(Decision::Notify(Reason::Escalated), decision);
+        assert_eq!(None, state.acknowledged);
+        assert_eq!(Some(later), state.unacknowledged_since);
+    }
+
+    #[test]
+    fn test_no_repeat_interval_never_repeats() {
+        let policy = Policy {
+            repeat_interval: None,
+            ..policy()
+        };
+
+        assert_eq!(
+            vec![
+                Decision::Notify(Reason::New),
+                Decision::Suppress,
+                Decision::Suppress,
+            ],
+            run(&policy, &[(0, WARNING), (60, WARNING), (600, WARNING)])
+        );
+    }
+
+    #[test]
+    fn test_repeat_interval_shorter_than_window() {
+        let policy = Policy {
+            suppression_window: Duration::minutes(10),
+            repeat_interval: Some(Duration::minutes(1)),
+        };
+
+        assert_eq!(
+            vec![
+                Decision::Notify(Reason::New),
+                Decision::Suppress,
+                Decision::Notify(Reason::Repeat),
+            ],
+            run(&policy, &[(0, WARNING), (2, WARNING), (10, WARNING)])
+        );
+    }
+
+    #[test]
+    fn test_escalation_notifies_within_window() {
+        assert_eq!(
+            vec![
+                Decision::Notify(Reason::New),
+                Decision::Notify(Reason::Escalated),
+                Decision::Suppress,
+            ],
+            run(&policy(), &[(0, WARNING), (1, CRITICAL), (2, CRITICAL)])
+        );
+    }
+
+    #[test]
+    fn test_deescalation_is_suppressed() {
+        assert_eq!(
+            vec![
+                Decision::Notify(Reason::New),
+                Decision::Suppress,
+                Decision::Suppress,
+                Decision::Notify(Reason::Repeat),
+            ],
+            run(
+                &policy(),
+                &[(0, CRITICAL), (1, WARNING), (2, INFO), (30, INFO)]
+            )
+        );
+    }
+
+    #[test]
+    fn test_escalating_back_after_deescalation_is_not_new() {
+        // The last notified severity stays critical while the incident
+        // dips to warning, so returning to critical is not an escalation.
+        assert_eq!(
+            vec![
+                Decision::Notify(Reason::New),
+                Decision::Suppress,
+                Decision::Suppress,
+            ],
+            run(&policy(), &[(0, CRITICAL), (1, WARNING), (2, CRITICAL)])
+        );
+    }
+
+    #[test]
+    fn test_resolution_is_notified_once() {
+        assert_eq!(
+            vec![
+                Decision::Notify(Reason::New),
+                Decision::Notify(Reason::Resolved),
+                Decision::Silent,
+            ],
+            run(
+                &policy(),
+                &[
+                    (0, WARNING),
+                    (10, Evaluation::Clear),
+                    (11, Evaluation::Clear)
+                ]
+            )
+        );
+    }
+
+    #[test]
+    fn test_refiring_within_window_is_suppressed() {
+        assert_eq!(
+            vec![
+                Decision::Notify(Reason::New),
+                Decision::Notify(Reason::Resolved),
+                Decision::Suppress,
+                Decision::Notify(Reason::New),
+            ],
+            run(
+                &policy(),
+                &[
+                    (0, WARNING),
+                    (1, Evaluation::Clear),
+                    (2, WARNING),
+                    (5, WARNING),
+                ]
+            )
+        );
+    }
+
+    #[test]
+    fn test_suppressed_incident_resolves_silently() {
+        assert_eq!(
+            vec![
+                Decision::Notify(Reason::New),
+                Decision::Notify(Reason::Resolved),
+                Decision::Suppress,
+                Decision::Silent,
+            ],
+            run(
+                &policy(),
+                &[
+                    (0, WARNING),
+                    (1, Evaluation::Clear),
+                    (2, WARNING),
+                    (3, Evaluation::Clear),
+                ]
+            )
+        );
+    }
+
+    #[test]
+    fn test_refiring_with_higher_severity_escalates_within_window() {
+        assert_eq!(
+            vec![
+                Decision::Notify(Reason::New),
+                Decision::Notify(Reason::Resolved),
+                Decision::Notify(Reason::Escalated),
+            ],
+            run(
+                &policy(),
+                &[(0, WARNING), (1, Evaluation::Clear), (2, CRITICAL)]
+            )
+        );
+    }
+
+    #[test]
+    fn test_refiring_after_window_is_new() {
+        assert_eq!(
+            vec![
+                Decision::Notify(Reason::New),
+                Decision::Notify(Reason::Resolved),
+                Decision::Notify(Reason::New),
+            ],
+            run(
+                &policy(),
+                &[(0, CRITICAL), (1, Evaluation::Clear), (6, WARNING)]
+            )
+        );
+    }
+
+    #[test]
+    fn test_zero_window_notifies_every_new_incident() {
+        let policy = Policy {
+            suppression_window: Duration::zero(),
+            repeat_interval: None,
+        };
+
+        assert_eq!(
+            vec![
+                Decision::Notify(Reason::New),
+                Decision::Notify(Reason::Resolved),
+                Decision::Notify(Reason::New),
+                Decision::Suppress,
+            ],
+            run(
+                &policy,
+                &[
+                    (0, WARNING),
+                    (0, Evaluation::Clear),
+                    (0, WARNING),
+                    (0, WARNING),
+                ]
+            )
+        );
+    }
+}
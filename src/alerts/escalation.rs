@@ -0,0 +1,203 @@
+//! Escalation chains: who is notified of an incident, and when, while
+//! nobody acknowledges it.
+
+use crate::alerts::antispam::NotifyState;
+use crate::alerts::routing::{Channel, Route};
+use crate::alerts::Severity;
+use crate::configure;
+use crate::errors::Error;
+use chrono::{DateTime, Duration, Utc};
+use serde::Deserialize;
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Step {
+    /// Minutes the incident has gone unacknowledged before this step is
+    /// notified. The first step is notified straight away.
+    #[serde(default)]
+    pub after: i64,
+    pub channels: Vec<Channel>,
+    /// Recipients in place of the channels' own, e.g. an on-call email
+    /// address.
+    #[serde(alias = "postmark_to")]
+    pub to: Option<String>,
+}
+
+fn default_min_severity() -> Severity {
+    Severity::Warning
+}
+
+/// The escalation chain configured under `escalation`. It replaces the
+/// routing table for alerts of `min_severity` and above.
+#[derive(Debug, Clone, Deserialize)]
+pub struct EscalationPolicy {
+    #[serde(default = "default_min_severity")]
+    pub min_severity: Severity,
+    pub steps: Vec<Step>,
+}
+
+impl EscalationPolicy {
+    /// Load the escalation chain from config, if there is one.
+    pub fn load() -> Result<Option<EscalationPolicy>, Error> {
+        let mut policy = match configure::fetch_section::<EscalationPolicy>("escalation")? {
+            Some(policy) => policy,
+            None => return Ok(None),
+        };
+
+        if policy.steps.is_empty() {
+            return Err(Error::Internal(String::from(
+                "Escalation needs at least one step",
+            )));
+        }
+        policy.steps.sort_by_key(|step| step.after);
+
+        Ok(Some(policy))
+    }
+
+    pub fn applies(&self, severity: Severity) -> bool {
+        severity >= self.min_severity
+    }
+
+    /// The step an incident unacknowledged for `elapsed` has reached.
+    fn due_step(&self, elapsed: Duration) -> usize {
+        self.steps
+            .iter()
+            .rposition(|step| Duration::minutes(step.after) <= elapsed)
+            .unwrap_or(0)
+    }
+
+    /// The step a notified, unacknowledged incident escalates to at `now`,
+    /// if it has gone past the step reached so far.
+    pub fn advance(&self, state: &NotifyState, now: DateTime<Utc>) -> Option<usize> {
+        if !state.firing || !state.notified || state.acknowledged.is_some() {
+            return None;
+        }
+
+        let since = state.unacknowledged_since?;
+        let step = self.due_step(now - since);
+
+        if step > state.escalation_step {
+            Some(step)
+        } else {
+            None
+        }
+    }
+
+    /// Every channel named in the chain.
+    pub fn named_channels(&self) -> Vec<&Channel> {
+        self.steps
+            .iter()
+            .flat_map(|step| step.channels.iter())
+            .collect()
+    }
+
+    /// Where to deliver notifications for the steps `from..=to`, each
+    /// channel and recipient once.
+    pub fn routes(&self, from: usize, to: usize) -> Vec<Route> {
+        let mut routes: Vec<Route> = Vec::new();
+        for step in self.steps.iter().take(to + 1).skip(from) {
+            for channel in &step.channels {
+                let route = Route {
+                    channel: channel.clone(),
+                    to: step.to.clone(),
+                };
+                if !routes.contains(&route) {
+                    routes.push(route);
+                }
+            }
+        }
+
+        routes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy() -> EscalationPolicy {
+        EscalationPolicy {
+            min_severity: Severity::Warning,
+            steps: vec![
+                Step {
+                    after: 0,
+                    channels: vec![Channel::new("slack")],
+                    to: None,
+                },
+                Step {
+                    after: 15,
+                    channels: vec![Channel::new("postmark")],
+                    to: Some(String::from("oncall@example.com")),
+                },
+                Step {
+                    after: 45,
+                    channels: vec![Channel::new("postmark")],
+                    to: Some(String::from("secondary@example.com")),
+                },
+            ],
+        }
+    }
+
+    #[test]
+    fn test_advance_while_unacknowledged() {
+        let start = Utc::now();
+        let mut state = NotifyState {
+            firing: true,
+            notified: true,
+            unacknowledged_since: Some(start),
+            ..NotifyState::default()
+        };
+
+        assert_eq!(
+            None,
+            policy().advance(&state, start + Duration::minutes(14))
+        );
+        assert_eq!(
+            Some(1),
+            policy().advance(&state, start + Duration::minutes(15))
+        );
+
+        state.escalation_step = 1;
+        assert_eq!(
+            None,
+            policy().advance(&state, start + Duration::minutes(30))
+        );
+        assert_eq!(
+            Some(2),
+            policy().advance(&state, start + Duration::minutes(60))
+        );
+
+        state.acknowledged = Some(crate::alerts::ack::Acknowledgement {
+            target: String::from("replica"),
+            name: String::from("replication_lag"),
+            by: String::from("dba"),
+            comment: String::new(),
+            at: start,
+        });
+        assert_eq!(
+            None,
+            policy().advance(&state, start + Duration::minutes(60))
+        );
+    }
+
+    #[test]
+    fn test_routes() {
+        assert_eq!(
+            vec![Route::new(Channel::new("slack"))],
+            policy().routes(0, 0)
+        );
+        assert_eq!(
+            vec![
+                Route::new(Channel::new("slack")),
+                Route {
+                    channel: Channel::new("postmark"),
+                    to: Some(String::from("oncall@example.com")),
+                },
+                Route {
+                    channel: Channel::new("postmark"),
+                    to: Some(String::from("secondary@example.com")),
+                },
+            ],
+            policy().routes(0, 2)
+        );
+    }
+}
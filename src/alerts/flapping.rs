@@ -0,0 +1,171 @@
+//! Flap detection: a replica whose alerts keep changing from one poll to
+//! the next, e.g. with lag hovering around a threshold, is reported once as
+//! flapping and then held back until it settles.
+
+use crate::configure;
+use crate::errors::Error;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, VecDeque};
+
+fn default_window() -> usize {
+    10
+}
+
+fn default_flap_threshold() -> f64 {
+    0.5
+}
+
+fn default_stable_threshold() -> f64 {
+    0.2
+}
+
+/// Flap detection settings, configured under `flapping`.
+///
+/// A replica starts flapping once the share of polls in the window on
+/// which its alerts changed reaches `flap_threshold`, and stops once it
+/// falls to `stable_threshold` or below.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct FlapPolicy {
+    /// Number of most recent polls considered.
+    #[serde(default = "default_window")]
+    pub window: usize,
+    #[serde(default = "default_flap_threshold")]
+    pub flap_threshold: f64,
+    #[serde(default = "default_stable_threshold")]
+    pub stable_threshold: f64,
+}
+
+impl FlapPolicy {
+    /// Load the policy from config; flap detection is off without one.
+    pub fn load() -> Result<Option<FlapPolicy>, Error> {
+        let policy = configure::fetch_section::<FlapPolicy>("flapping")?;
+        if let Some(policy) = &policy {
+            if policy.window < 2 || policy.stable_threshold >= policy.flap_threshold {
+                return Err(Error::Internal(format!(
+                    "Invalid flapping config, it needs a window of 2 or more polls and a stable_threshold below flap_threshold: {:?}",
+                    policy
+                )));
+            }
+        }
+
+        Ok(policy)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Flapping {
+    /// The replica started flapping on this poll.
+    Started(f64),
+    /// Still flapping.
+    Ongoing,
+    /// The replica settled on this poll.
+    Stopped,
+    /// Not flapping.
+    No,
+}
+
+/// The recent history of one replica's alerts, saved in the state store
+/// when the replica starts or stops flapping, so that a restart in between
+/// still announces that it settled.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct FlapDetector {
+    previous: Option<BTreeSet<String>>,
+    /// Whether the alerts changed, per poll, oldest first.
+    changes: VecDeque<bool>,
+    flapping: bool,
+}
+
+impl FlapDetector {
+    pub fn is_flapping(&self) -> bool {
+        self.flapping
+    }
+
+    /// Share of the polls in the window on which the alerts changed.
+    fn change_rate(&self, policy: &FlapPolicy) -> f64 {
+        let changes = self.changes.iter().filter(|changed| **changed).count();
+
+        changes as f64 / policy.window as f64
+    }
+
+    /// Record the names of the alerts firing on the latest poll.
+    pub fn record(&mut self, firing: BTreeSet<String>, policy: &FlapPolicy) -> Flapping {
+        let changed = self
+            .previous
+            .as_ref()
+            .is_some_and(|previous| previous != &firing);
+        self.previous = Some(firing);
+
+        self.changes.push_back(changed);
+        while self.changes.len() > policy.window {
+            self.changes.pop_front();
+        }
+
+        let rate = self.change_rate(policy);
+        match (self.flapping, rate) {
+            (false, rate) if rate >= policy.flap_threshold => {
+                self.flapping = true;
+                Flapping::Started(rate)
+            }
+            (true, rate) if rate <= policy.stable_threshold => {
+                self.flapping = false;
+                Flapping::Stopped
+            }
+            (true, _) => Flapping::Ongoing,
+            (false, _) => Flapping::No,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy() -> FlapPolicy {
+        FlapPolicy {
+            window: 4,
+            flap_threshold: 0.75,
+            stable_threshold: 0.25,
+        }
+    }
+
+    fn firing(alerting: bool) -> BTreeSet<String> {
+        if alerting {
+            vec![String::from("replication_lag")].into_iter().collect()
+        } else {
+            BTreeSet::new()
+        }
+    }
+
+    #[test]
+    fn test_flapping_starts_and_stops() {
+        let mut detector = FlapDetector::default();
+        let flaps: Vec<Flapping> = [true, false, true, false, true, true, true, true, true, true]
+            .iter()
+            .map(|alerting| detector.record(firing(*alerting), &policy()))
+            .collect();
+
+        assert_eq!(
+            vec![
+                Flapping::No,
+                Flapping::No,
+                Flapping::No,
+                Flapping::Started(0.75),
+                Flapping::Ongoing,
+                Flapping::Ongoing,
+                Flapping::Ongoing,
+                Flapping::Stopped,
+                Flapping::No,
+                Flapping::No,
+            ],
+            flaps
+        );
+    }
+
+    #[test]
+    fn test_steady_alert_is_not_flapping() {
+        let mut detector = FlapDetector::default();
+        for _ in 0..10 {
+            assert_eq!(Flapping::No, detector.record(firing(true), &policy()));
+        }
+    }
+}
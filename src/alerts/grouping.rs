@@ -0,0 +1,150 @@
+//! Alert grouping: when a primary goes down every replica behind it alerts
+//! at once, so alerts sharing a group key are held for a short window and
+//! sent as one summary.
+
+use crate::configure;
+use crate::errors::Error;
+use chrono::{DateTime, Duration, Utc};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+
+/// What alerts are grouped by.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GroupBy {
+    /// The replica's `master_host`.
+    MasterHost,
+    /// The `cluster` label of the replica.
+    Cluster,
+}
+
+/// Grouping settings, configured under `grouping`.
+#[derive(Debug, Clone, Deserialize)]
+struct GroupingConfig {
+    by: GroupBy,
+    /// Seconds to hold the first alert of a group for others to join.
+    #[serde(default)]
+    window: i64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct GroupingPolicy {
+    pub by: GroupBy,
+    pub window: Duration,
+}
+
+impl GroupingPolicy {
+    /// Load the policy from config; alerts are not grouped without one.
+    pub fn load() -> Result<Option<GroupingPolicy>, Error> {
+        Ok(
+            configure::fetch_section::<GroupingConfig>("grouping")?.map(|config| GroupingPolicy {
+                by: config.by,
+                window: Duration::seconds(config.window),
+            }),
+        )
+    }
+
+    /// The group of an alert on `target`. A replica without a master host or
+    /// cluster label is a group of its own.
+    pub fn key(&self, target: &str, cluster: Option<&str>, master_host: &str) -> String {
+        let key = match self.by {
+            GroupBy::MasterHost if !master_host.is_empty() => Some(master_host),
+            GroupBy::MasterHost => None,
+            GroupBy::Cluster => cluster,
+        };
+
+        match key {
+            Some(key) => String::from(key),
+            None => format!("replica {}", target),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Group<A> {
+    opened_at: DateTime<Utc>,
+    alerts: Vec<A>,
+}
+
+/// Alerts held back for their group's window.
+#[derive(Debug)]
+pub struct Grouper<A> {
+    window: Duration,
+    groups: BTreeMap<String, Group<A>>,
+}
+
+impl<A> Grouper<A> {
+    pub fn new(policy: &GroupingPolicy) -> Grouper<A> {
+        Grouper {
+            window: policy.window,
+            groups: BTreeMap::new(),
+        }
+    }
+
+    pub fn add(&mut self, key: String, alert: A, now: DateTime<Utc>) {
+        self.groups
+            .entry(key)
+            .or_insert_with(|| Group {
+                opened_at: now,
+                alerts: Vec::new(),
+            })
+            .alerts
+            .push(alert);
+    }
+
+    /// Take the groups whose window is over, with their key.
+    pub fn take_due(&mut self, now: DateTime<Utc>) -> Vec<(String, Vec<A>)> {
+        let due: Vec<String> = self
+            .groups
+            .iter()
+            .filter(|(_, group)| now - group.opened_at >= self.window)
+            .map(|(key, _)| key.clone())
+            .collect();
+
+        due.into_iter()
+            .filter_map(|key| self.groups.remove(&key).map(|group| (key, group.alerts)))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_key() {
+        let policy = GroupingPolicy {
+            by: GroupBy::MasterHost,
+            window: Duration::zero(),
+        };
+
+        assert_eq!("db-1", policy.key("replica", Some("main"), "db-1"));
+        assert_eq!("replica replica", policy.key("replica", Some("main"), ""));
+
+        let policy = GroupingPolicy {
+            by: GroupBy::Cluster,
+            ..policy
+        };
+        assert_eq!("main", policy.key("replica", Some("main"), "db-1"));
+    }
+
+    #[test]
+    fn test_groups_are_held_for_window() {
+        let start = Utc::now();
+        let mut grouper = Grouper::new(&GroupingPolicy {
+            by: GroupBy::MasterHost,
+            window: Duration::seconds(60),
+        });
+
+        grouper.add(String::from("db-1"), "a", start);
+        grouper.add(String::from("db-2"), "c", start);
+        grouper.add(String::from("db-1"), "b", start + Duration::seconds(30));
+        assert!(grouper.take_due(start + Duration::seconds(59)).is_empty());
+
+        let due = grouper.take_due(start + Duration::seconds(60));
+        assert_eq!(2, due.len());
+        assert_eq!("db-1", due[0].0);
+        assert_eq!(vec!["a", "b"], due[0].1);
+        assert!(grouper.take_due(start + Duration::seconds(120)).is_empty());
+    }
+}
@@ -1,5 +1,84 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+pub mod ack;
+pub mod antispam;
+pub mod escalation;
+pub mod flapping;
+pub mod grouping;
 pub mod queue;
+pub mod routing;
+pub mod silence;
+pub mod state;
+pub mod store;
+
+/// What an alert is about; it decides the headline of the notification.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub enum AlertType {
+    /// Replication is broken, erroring or lagging.
+    #[default]
+    Replication,
+    /// The replica's lag cannot be determined, e.g. `Seconds_Behind_Master`
+    /// is NULL.
+    UnknownLag,
+    /// Sentinel could not query the replica at all.
+    Unreachable,
+    /// The replica's alerts keep changing from poll to poll.
+    Flapping,
+}
+
+impl fmt::Display for AlertType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            AlertType::Replication => "Replication",
+            AlertType::UnknownLag => "Unknown lag",
+            AlertType::Unreachable => "Replica unreachable",
+            AlertType::Flapping => "Flapping",
+        };
 
+        write!(f, "{}", name)
+    }
+}
+
+/// How urgently an alert needs attention.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Info,
+    #[default]
+    Warning,
+    Critical,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Severity::Info => "info",
+            Severity::Warning => "warning",
+            Severity::Critical => "critical",
+        };
+
+        write!(f, "{}", name)
+    }
+}
+
+/// A problem with the values a replica reported, rather than with
+/// replication itself.
+#[derive(Debug, Clone)]
 pub struct QueryAlert {
+    pub alert_type: AlertType,
     pub warning: String,
 }
+
+impl QueryAlert {
+    /// When replication is stopped with `STOP SLAVE;` the server reports
+    /// `Seconds_Behind_Master: NULL`, which must never read as "no lag".
+    pub fn unknown_lag() -> QueryAlert {
+        QueryAlert {
+            alert_type: AlertType::UnknownLag,
+            warning: String::from(
+                "DB Slave returned `Seconds_Behind_Master: NULL`, replication lag is unknown",
+            ),
+        }
+    }
+}
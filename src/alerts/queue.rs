@@ -1,6 +1,5 @@
-use crate::dbslave::DBSlaveStatus;
 use crate::errors::Error;
-use crate::monitor::{Alert, SentAlerts};
+use crate::monitor::Alert;
 use std::fmt;
 
 #[derive(Default)]
@@ -30,6 +29,10 @@ impl<T> AlertQueue<T> {
         Ok(self.queue.len())
     }
 
+    pub fn is_empty(&self) -> bool {
+        self.queue.is_empty()
+    }
+
     pub fn take_first(&mut self) -> Result<Alert<T>, Error> {
         let first = self.queue.remove(0);
 
@@ -45,5 +48,3 @@ where
 
     Ok(main_queue)
 }
-
-
@@ -0,0 +1,109 @@
+use crate::alerts::Severity;
+use crate::configure;
+use crate::errors::Error;
+use serde::Deserialize;
+use std::fmt;
+
+/// The name of a notification channel an alert can be routed to, as listed
+/// under `channels`, e.g. `slack` or `postmark`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Deserialize)]
+#[serde(transparent)]
+pub struct Channel(pub String);
+
+impl Channel {
+    pub fn new(name: &str) -> Channel {
+        Channel(String::from(name))
+    }
+}
+
+impl fmt::Display for Channel {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// One delivery of a notification.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Route {
+    pub channel: Channel,
+    /// Recipients in place of the channel's own, e.g. email addresses.
+    pub to: Option<String>,
+}
+
+impl Route {
+    pub fn new(channel: Channel) -> Route {
+        Route { channel, to: None }
+    }
+}
+
+/// Which channels receive alerts of each severity, as configured under
+/// `routing`.
+///
+/// A severity left out of the config goes to every channel, so that no alert
+/// is dropped by omission; list it with `[]` to silence it.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct RoutingTable {
+    pub info: Option<Vec<Channel>>,
+    pub warning: Option<Vec<Channel>>,
+    pub critical: Option<Vec<Channel>>,
+}
+
+impl RoutingTable {
+    /// Load the routing table from config; every severity goes to every
+    /// channel when none is configured.
+    pub fn load() -> Result<RoutingTable, Error> {
+        Ok(configure::fetch_section::<RoutingTable>("routing")?.unwrap_or_default())
+    }
+
+    /// Every channel named in the table.
+    pub fn named_channels(&self) -> Vec<&Channel> {
+        vec![&self.info, &self.warning, &self.critical]
+            .into_iter()
+            .flatten()
+            .flatten()
+            .collect()
+    }
+
+    /// The channels for `severity`, out of all channels `all`.
+    pub fn channels<'a>(&'a self, severity: Severity, all: &'a [Channel]) -> &'a [Channel] {
+        let channels = match severity {
+            Severity::Info => &self.info,
+            Severity::Warning => &self.warning,
+            Severity::Critical => &self.critical,
+        };
+
+        match channels {
+            Some(channels) => channels,
+            None => all,
+        }
+    }
+
+    pub fn routes(&self, severity: Severity, all: &[Channel]) -> Vec<Route> {
+        self.channels(severity, all)
+            .iter()
+            .map(|channel| Route::new(channel.clone()))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_routes_by_severity() {
+        let all = vec![Channel::new("slack"), Channel::new("postmark")];
+        let routing = RoutingTable {
+            info: Some(vec![]),
+            warning: Some(vec![Channel::new("slack")]),
+            critical: None,
+        };
+
+        assert!(routing.routes(Severity::Info, &all).is_empty());
+        assert_eq!(
+            vec![Route::new(Channel::new("slack"))],
+            routing.routes(Severity::Warning, &all)
+        );
+        assert_eq!(all, routing.channels(Severity::Critical, &all));
+    }
+}
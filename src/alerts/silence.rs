@@ -0,0 +1,235 @@
+use crate::alerts::Severity;
+use crate::configure;
+use crate::errors::Error;
+use chrono::{DateTime, Datelike, Duration, NaiveTime, Utc, Weekday};
+use serde::Deserialize;
+use std::path::Path;
+
+const DEFAULT_RUNTIME_PATH: &str = "state/silences.yml";
+
+/// A recurring window in UTC, e.g. Sundays 02:00–04:00. A window whose end
+/// is before its start runs past midnight into the next day.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Schedule {
+    /// Days the window starts on, e.g. `["sun"]`; every day when empty.
+    #[serde(default)]
+    pub days: Vec<Weekday>,
+    /// Start time of day, e.g. `02:00`.
+    pub start: String,
+    /// End time of day, e.g. `04:00`.
+    pub end: String,
+}
+
+fn parse_time(time: &str) -> Result<NaiveTime, Error> {
+    NaiveTime::parse_from_str(time, "%H:%M")
+        .or_else(|_| NaiveTime::parse_from_str(time, "%H:%M:%S"))
+        .map_err(|_| Error::Internal(format!("Invalid time in silence schedule: {}", time)))
+}
+
+impl Schedule {
+    fn starts_on(&self, day: Weekday) -> bool {
+        self.days.is_empty() || self.days.contains(&day)
+    }
+
+    pub fn is_active(&self, now: DateTime<Utc>) -> Result<bool, Error> {
+        let (start, end) = (parse_time(&self.start)?, parse_time(&self.end)?);
+        let time = now.time();
+        let today = now.weekday();
+        let yesterday = (now - Duration::days(1)).weekday();
+
+        let active = if start < end {
+            self.starts_on(today) && time >= start && time < end
+        } else {
+            (self.starts_on(today) && time >= start) || (self.starts_on(yesterday) && time < end)
+        };
+
+        Ok(active)
+    }
+}
+
+/// Holds back notifications of matching alerts, which are still recorded.
+///
+/// Every matcher left out matches anything; a silence without `starts_at`,
+/// `ends_at` or `schedule` is always active.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Silence {
+    #[serde(default)]
+    pub comment: String,
+    pub target: Option<String>,
+    /// Name of the rule or check that raised the alert.
+    pub rule: Option<String>,
+    pub severity: Option<Severity>,
+    pub starts_at: Option<DateTime<Utc>>,
+    pub ends_at: Option<DateTime<Utc>>,
+    pub schedule: Option<Schedule>,
+}
+
+impl Silence {
+    fn validate(&self) -> Result<(), Error> {
+        if let Some(schedule) = &self.schedule {
+            parse_time(&schedule.start)?;
+            parse_time(&schedule.end)?;
+        }
+
+        Ok(())
+    }
+
+    pub fn is_active(&self, now: DateTime<Utc>) -> bool {
+        if self.starts_at.is_some_and(|starts_at| now < starts_at)
+            || self.ends_at.is_some_and(|ends_at| now >= ends_at)
+        {
+            return false;
+        }
+
+        match &self.schedule {
+            Some(schedule) => schedule.is_active(now).unwrap_or(false),
+            None => true,
+        }
+    }
+
+    pub fn matches(
+        &self,
+        target: &str,
+        rule: &str,
+        severity: Severity,
+        now: DateTime<Utc>,
+    ) -> bool {
+        self.target
+            .as_deref()
+            .is_none_or(|silenced| silenced == target)
+            && self.rule.as_deref().is_none_or(|silenced| silenced == rule)
+            && self.severity.is_none_or(|silenced| silenced == severity)
+            && self.is_active(now)
+    }
+}
+
+/// Silences listed under `silences` in the config, typically recurring
+/// maintenance windows.
+pub fn configured() -> Result<Vec<Silence>, Error> {
+    let silences = configure::fetch_section::<Vec<Silence>>("silences")?.unwrap_or_default();
+    for silence in &silences {
+        silence.validate()?;
+    }
+
+    Ok(silences)
+}
+
+/// The path of the runtime silences file, `silences_file` in the config.
+pub fn runtime_path() -> Result<String, Error> {
+    let path = configure::fetch::<String>(String::from("silences_file"))?;
+
+    Ok(if path.is_empty() {
+        String::from(DEFAULT_RUNTIME_PATH)
+    } else {
+        path
+    })
+}
+
+/// Ad-hoc silences from the runtime silences file, which is read again on
+/// every poll so that silences can be added and lifted without a restart.
+///
+/// The file holds a `silences` list in the same form as the config, and
+/// missing it means no ad-hoc silences.
+pub fn runtime(path: &str) -> Result<Vec<Silence>, Error> {
+    if !Path::new(path).exists() {
+        return Ok(Vec::new());
+    }
+
+    let mut settings = config::Config::default();
+    settings.merge(config::File::with_name(path))?;
+    let silences = match settings.get::<Vec<Silence>>("silences") {
+        Ok(silences) => silences,
+        Err(config::ConfigError::NotFound(_)) => Vec::new(),
+        Err(error) => return Err(Error::from(error)),
+    };
+    for silence in &silences {
+        silence.validate()?;
+    }
+
+    Ok(silences)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(timestamp: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(timestamp)
+            .unwrap()
+            .with_timezone(&Utc)
+    }
+
+    fn silence() -> Silence {
+        Silence {
+            comment: String::new(),
+            target: None,
+            rule: None,
+            severity: None,
+            starts_at: None,
+            ends_at: None,
+            schedule: None,
+        }
+    }
+
+    #[test]
+    fn test_matchers() {
+        let now = at("2020-06-07T03:00:00Z");
+        let silence = Silence {
+            target: Some(String::from("shard-1")),
+            severity: Some(Severity::Warning),
+            ..silence()
+        };
+
+        assert!(silence.matches("shard-1", "replication_lag", Severity::Warning, now));
+        assert!(!silence.matches("shard-2", "replication_lag", Severity::Warning, now));
+        assert!(!silence.matches("shard-1", "replication_lag", Severity::Critical, now));
+    }
+
+    #[test]
+    fn test_fixed_window() {
+        let silence = Silence {
+            starts_at: Some(at("2020-06-07T02:00:00Z")),
+            ends_at: Some(at("2020-06-07T04:00:00Z")),
+            ..silence()
+        };
+
+        assert!(!silence.is_active(at("2020-06-07T01:59:59Z")));
+        assert!(silence.is_active(at("2020-06-07T02:00:00Z")));
+        assert!(!silence.is_active(at("2020-06-07T04:00:00Z")));
+    }
+
+    #[test]
+    fn test_recurring_window() {
+        // 2020-06-07 is a Sunday.
+        let silence = Silence {
+            schedule: Some(Schedule {
+                days: vec![Weekday::Sun],
+                start: String::from("02:00"),
+                end: String::from("04:00"),
+            }),
+            ..silence()
+        };
+
+        assert!(silence.is_active(at("2020-06-07T03:00:00Z")));
+        assert!(silence.is_active(at("2020-06-14T02:00:00Z")));
+        assert!(!silence.is_active(at("2020-06-07T04:00:00Z")));
+        assert!(!silence.is_active(at("2020-06-08T03:00:00Z")));
+    }
+
+    #[test]
+    fn test_recurring_window_past_midnight() {
+        let silence = Silence {
+            schedule: Some(Schedule {
+                days: vec![Weekday::Sat],
+                start: String::from("23:00"),
+                end: String::from("01:00"),
+            }),
+            ..silence()
+        };
+
+        assert!(silence.is_active(at("2020-06-06T23:30:00Z")));
+        assert!(silence.is_active(at("2020-06-07T00:30:00Z")));
+        assert!(!silence.is_active(at("2020-06-07T23:30:00Z")));
+        assert!(!silence.is_active(at("2020-06-06T00:30:00Z")));
+    }
+}
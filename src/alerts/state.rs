@@ -0,0 +1,135 @@
+use crate::alerts::{AlertType, Severity};
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub enum Status {
+    #[default]
+    Ok,
+    Firing,
+    /// Recovered since the previous poll; the recovery has been announced.
+    Resolved,
+}
+
+impl fmt::Display for Status {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Status::Ok => "OK",
+            Status::Firing => "FIRING",
+            Status::Resolved => "RESOLVED",
+        };
+
+        write!(f, "{}", name)
+    }
+}
+
+/// A finished incident, reported in the recovery notification.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Incident {
+    pub alert_type: AlertType,
+    pub severity: Severity,
+    pub started_at: DateTime<Utc>,
+    pub duration: Duration,
+    /// Highest lag in seconds seen while firing, if it was ever known.
+    pub peak_lag: Option<u64>,
+}
+
+/// The lifecycle of one named alert on one replica:
+/// `OK → FIRING → RESOLVED → OK`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct AlertState {
+    pub status: Status,
+    pub alert_type: AlertType,
+    pub severity: Severity,
+    pub started_at: Option<DateTime<Utc>>,
+    pub peak_lag: Option<u64>,
+}
+
+impl AlertState {
+    /// Record a poll on which the alert fired.
+    pub fn fire(
+        &mut self,
+        alert_type: AlertType,
+        severity: Severity,
+        lag: Option<u64>,
+        now: DateTime<Utc>,
+    ) {
+        if self.status != Status::Firing {
+            self.status = Status::Firing;
+            self.started_at = Some(now);
+            self.severity = severity;
+            self.peak_lag = None;
+        }
+
+        self.alert_type = alert_type;
+        self.severity = self.severity.max(severity);
+        self.peak_lag = self.peak_lag.max(lag);
+    }
+
+    /// Record a poll on which the alert did not fire, returning the incident
+    /// if this ends one.
+    pub fn clear(&mut self, now: DateTime<Utc>) -> Option<Incident> {
+        match self.status {
+            Status::Firing => {
+                let started_at = self.started_at.unwrap_or(now);
+                let incident = Incident {
+                    alert_type: self.alert_type,
+                    severity: self.severity,
+                    started_at,
+                    duration: now - started_at,
+                    peak_lag: self.peak_lag,
+                };
+
+                *self = AlertState {
+                    status: Status::Resolved,
+                    ..AlertState::default()
+                };
+                Some(incident)
+            }
+            Status::Resolved | Status::Ok => {
+                self.status = Status::Ok;
+                None
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lifecycle() {
+        let start = Utc::now();
+        let mut state = AlertState::default();
+
+        assert_eq!(None, state.clear(start));
+        assert_eq!(Status::Ok, state.status);
+
+        state.fire(AlertType::Replication, Severity::Warning, Some(400), start);
+        state.fire(
+            AlertType::Replication,
+            Severity::Critical,
+            Some(900),
+            start + Duration::minutes(5),
+        );
+        state.fire(
+            AlertType::Replication,
+            Severity::Warning,
+            None,
+            start + Duration::minutes(10),
+        );
+        assert_eq!(Status::Firing, state.status);
+
+        let incident = state.clear(start + Duration::minutes(15)).unwrap();
+        assert_eq!(Status::Resolved, state.status);
+        assert_eq!(start, incident.started_at);
+        assert_eq!(Duration::minutes(15), incident.duration);
+        assert_eq!(Some(900), incident.peak_lag);
+        assert_eq!(Severity::Critical, incident.severity);
+
+        assert_eq!(None, state.clear(start + Duration::minutes(20)));
+        assert_eq!(Status::Ok, state.status);
+    }
+}
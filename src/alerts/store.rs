@@ -0,0 +1,353 @@
+//! An append-only JSON log of alert state, flapping replicas and sent
+//! alerts, so that a restarted Sentinel carries on where it left off
+//! instead of re-sending every alert.
+//!
+//! Each line is one `Record`; later records for the same alert supersede
+//! earlier ones. The log is compacted each time it is opened, and again
+//! every `COMPACT_AFTER` appended records so that it stays small in a
+//! long-running process.
+
+use crate::alerts::antispam::NotifyState;
+use crate::alerts::flapping::FlapDetector;
+use crate::alerts::state::AlertState;
+use crate::alerts::Severity;
+use crate::configure;
+use crate::errors::Error;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+
+const DEFAULT_PATH: &str = "state/alerts.jsonl";
+
+/// Sent alerts kept when the log is compacted.
+const SENT_HISTORY_LIMIT: usize = 100;
+
+/// Records appended between two compactions.
+const COMPACT_AFTER: usize = 1000;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SentRecord {
+    pub target: String,
+    pub name: String,
+    pub severity: Severity,
+    pub resolved: bool,
+    /// Held back by a silence rather than notified.
+    #[serde(default)]
+    pub silenced: bool,
+    pub created_at: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum Record {
+    /// The state of one named alert on one replica.
+    State {
+        target: String,
+        name: String,
+        alert: AlertState,
+        notify: NotifyState,
+    },
+    /// The flap history of one replica, when it starts or stops flapping.
+    Flapping {
+        target: String,
+        detector: FlapDetector,
+    },
+    Sent(SentRecord),
+}
+
+/// Everything recovered from the log.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Snapshot {
+    /// Latest state per `(target, alert name)`.
+    pub states: BTreeMap<(String, String), (AlertState, NotifyState)>,
+    /// Latest flap history per target.
+    pub flapping: BTreeMap<String, FlapDetector>,
+    /// Most recent sent alerts, oldest first.
+    pub sent: Vec<SentRecord>,
+}
+
+impl Snapshot {
+    fn apply(&mut self, record: Record) {
+        match record {
+            Record::State {
+                target,
+                name,
+                alert,
+                notify,
+            } => {
+                self.states.insert((target, name), (alert, notify));
+            }
+            Record::Flapping { target, detector } => {
+                self.flapping.insert(target, detector);
+            }
+            Record::Sent(sent) => self.sent.push(sent),
+        }
+    }
+
+    /// Drop all but the latest `SENT_HISTORY_LIMIT` sent alerts.
+    fn trim(&mut self) {
+        let skip = self.sent.len().saturating_sub(SENT_HISTORY_LIMIT);
+        self.sent.drain(..skip);
+    }
+
+    fn records(&self) -> Vec<Record> {
+        let states = self
+            .states
+            .iter()
+            .map(|((target, name), (alert, notify))| Record::State {
+                target: target.clone(),
+                name: name.clone(),
+                alert: alert.clone(),
+                notify: notify.clone(),
+            });
+        let flapping = self
+            .flapping
+            .iter()
+            .map(|(target, detector)| Record::Flapping {
+                target: target.clone(),
+                detector: detector.clone(),
+            });
+        let sent = self.sent.iter().cloned().map(Record::Sent);
+
+        states.chain(flapping).chain(sent).collect()
+    }
+}
+
+#[derive(Debug)]
+pub struct Store {
+    path: PathBuf,
+    file: File,
+    /// What the log holds, to compact it without reading it back.
+    snapshot: Snapshot,
+    /// Records appended since the last compaction.
+    appended: usize,
+}
+
+fn write_record(file: &mut File, record: &Record) -> Result<(), Error> {
+    let line = serde_json::to_string(record)?;
+    writeln!(file, "{}", line)?;
+
+    Ok(())
+}
+
+/// Rewrite the log at `path` as `snapshot` and open it for appending.
+fn compact(path: &Path, snapshot: &Snapshot) -> Result<File, Error> {
+    // Write the compacted log aside and swap it in, so that a crash while
+    // compacting never loses the previous log.
+    let compacted = path.with_extension("jsonl.tmp");
+    let mut file = File::create(&compacted)?;
+    for record in snapshot.records() {
+        write_record(&mut file, &record)?;
+    }
+    file.sync_all()?;
+    fs::rename(&compacted, path)?;
+
+    Ok(OpenOptions::new().append(true).open(path)?)
+}
+
+impl Store {
+    /// Open the log at `state_file`, or `state/alerts.jsonl` by default.
+    pub fn load() -> Result<(Store, Snapshot), Error> {
+        let path = configure::fetch::<String>(String::from("state_file"))?;
+        let path = if path.is_empty() {
+            String::from(DEFAULT_PATH)
+        } else {
+            path
+        };
+
+        Store::open(Path::new(&path))
+    }
+
+    /// Read the log, compact it and open it for appending.
+    ///
+    /// A line that cannot be parsed, e.g. one cut short by a crash, is
+    /// skipped rather than failing startup.
+    pub fn open(path: &Path) -> Result<(Store, Snapshot), Error> {
+        let mut snapshot = Snapshot::default();
+
+        if path.exists() {
+            let reader = BufReader::new(File::open(path)?);
+            for (number, line) in reader.lines().enumerate() {
+                let line = line?;
+                if line.trim().is_empty() {
+                    continue;
+                }
+
+                match serde_json::from_str::<Record>(&line) {
+                    Ok(record) => snapshot.apply(record),
+                    Err(error) => warn!(
+                        "Skipping line {} of {}: {}",
+                        number + 1,
+                        path.display(),
+                        error
+                    ),
+                }
+            }
+        }
+
+        snapshot.trim();
+
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        let file = compact(path, &snapshot)?;
+        let store = Store {
+            path: path.to_path_buf(),
+            file,
+            snapshot: snapshot.clone(),
+            appended: 0,
+        };
+
+        Ok((store, snapshot))
+    }
+
+    pub fn append(&mut self, record: &Record) -> Result<(), Error> {
+        write_record(&mut self.file, record)?;
+        self.file.flush()?;
+
+        self.snapshot.apply(record.clone());
+        self.snapshot.trim();
+        self.appended += 1;
+        if self.appended >= COMPACT_AFTER {
+            self.file = compact(&self.path, &self.snapshot)?;
+            self.appended = 0;
+        }
+
+        Ok(())
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::alerts::state::Status;
+
+    fn temp_path(name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!(
+            "sentinel-store-{}-{}.jsonl",
+            name,
+            std::process::id()
+        ));
+        let _ = fs::remove_file(&path);
+
+        path
+    }
+
+    fn sent(index: usize) -> Record {
+        Record::Sent(SentRecord {
+            target: String::from("replica"),
+            name: String::from("replication_lag"),
+            severity: Severity::Warning,
+            resolved: false,
+            silenced: false,
+            created_at: index.to_string(),
+        })
+    }
+
+    #[test]
+    fn test_reload_keeps_latest_state() {
+        let path = temp_path("reload");
+        let firing = AlertState {
+            status: Status::Firing,
+            ..AlertState::default()
+        };
+        let notify = NotifyState {
+            firing: true,
+            notified: true,
+            ..NotifyState::default()
+        };
+
+        {
+            let (mut store, snapshot) = Store::open(&path).unwrap();
+            assert_eq!(Snapshot::default(), snapshot);
+
+            for (alert, notify) in &[
+                (AlertState::default(), NotifyState::default()),
+                (firing.clone(), notify.clone()),
+            ] {
+                store
+                    .append(&Record::State {
+                        target: String::from("replica"),
+                        name: String::from("replication_lag"),
+                        alert: alert.clone(),
+                        notify: notify.clone(),
+                    })
+                    .unwrap();
+            }
+            store.append(&sent(0)).unwrap();
+        }
+
+        // A line cut short by a crash is skipped.
+        let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+        write!(file, "{{\"kind\":\"sta").unwrap();
+
+        let (_, snapshot) = Store::open(&path).unwrap();
+        let key = (String::from("replica"), String::from("replication_lag"));
+        assert_eq!(Some(&(firing, notify)), snapshot.states.get(&key));
+        assert_eq!(1, snapshot.sent.len());
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_compaction_limits_sent_history() {
+        let path = temp_path("compaction");
+
+        {
+            let (mut store, _) = Store::open(&path).unwrap();
+            for index in 0..SENT_HISTORY_LIMIT + 10 {
+                store.append(&sent(index)).unwrap();
+            }
+        }
+
+        let (_, snapshot) = Store::open(&path).unwrap();
+        assert_eq!(SENT_HISTORY_LIMIT, snapshot.sent.len());
+        assert_eq!(
+            sent(10),
+            Record::Sent(snapshot.sent.first().unwrap().clone())
+        );
+
+        let lines = fs::read_to_string(&path).unwrap().lines().count();
+        assert_eq!(SENT_HISTORY_LIMIT, lines);
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_compacts_while_running() {
+        let path = temp_path("running");
+        let (mut store, _) = Store::open(&path).unwrap();
+
+        for index in 0..COMPACT_AFTER + 5 {
+            store
+                .append(&Record::State {
+                    target: String::from("replica"),
+                    name: String::from("replication_lag"),
+                    alert: AlertState::default(),
+                    notify: NotifyState::default(),
+                })
+                .unwrap();
+            store.append(&sent(index)).unwrap();
+        }
+
+        // One state and the sent history, plus what was appended since.
+        let lines = fs::read_to_string(&path).unwrap().lines().count();
+        assert!(lines <= 1 + SENT_HISTORY_LIMIT + 10, "{} lines", lines);
+
+        let (_, snapshot) = Store::open(&path).unwrap();
+        assert_eq!(1, snapshot.states.len());
+        assert_eq!(
+            sent(COMPACT_AFTER + 4),
+            Record::Sent(snapshot.sent.last().unwrap().clone())
+        );
+
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -1,4 +1,5 @@
 use super::errors::Error;
+use serde::de::DeserializeOwned;
 use std::collections::HashMap;
 
 pub struct ConfigInfo<V> {
@@ -57,11 +58,36 @@ where
     // to allow it to call `fetch_config` as defined by the trait, returning T.
     ConfigInfo<String>: FetchFromConfig<T>,
 {
-    let config = match crate::CONFIG.clone().try_into::<HashMap<String, String>>() {
+    let config = match crate::CONFIG
+        .clone()
+        .try_into::<HashMap<String, config::Value>>()
+    {
         Ok(config) => config,
         Err(error) => panic!("Error: {:?}", error),
     };
 
+    // Only scalar values are exposed as flags; lists and tables are read
+    // through `fetch_section`.
+    let config: HashMap<String, String> = config
+        .into_iter()
+        .filter_map(|(key, value)| value.into_str().ok().map(|value| (key, value)))
+        .collect();
+
     let cli_info = ConfigInfo { flag };
     Ok(cli_info.fetch_config(&config))
 }
+
+/// Deserialize a structured configuration section, such as a list of
+/// replica targets.
+///
+/// Returns `Ok(None)` when the section is not present in the config.
+pub fn fetch_section<T>(key: &str) -> Result<Option<T>, Error>
+where
+    T: DeserializeOwned,
+{
+    match crate::CONFIG.get::<T>(key) {
+        Ok(section) => Ok(Some(section)),
+        Err(config::ConfigError::NotFound(_)) => Ok(None),
+        Err(error) => Err(Error::from(error)),
+    }
+}
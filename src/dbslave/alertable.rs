@@ -1,48 +1,453 @@
+use crate::alerts::state::Incident;
+use crate::alerts::{AlertType, QueryAlert, Severity};
 use crate::dbslave;
+use crate::dbslave::gtid::{GtidSet, GtidStall};
+use crate::dbslave::replication_error::{self, ErrnoRule, ErrorClass};
+use crate::dbslave::rules::RuleMatch;
 use crate::errors::Error;
 use crate::utils;
 use chrono::Utc;
 
-pub async fn run(slave_data: &mut dbslave::DBSlaveStatus) -> Result<(bool, String), Error> {
-    let mut alertable: bool = false;
+/// A reason to alert on a replica. Each is named, e.g. after the rule that
+/// raised it, and throttled separately from the others.
+#[derive(Debug, Clone)]
+pub struct Firing {
+    pub name: String,
+    pub alert_type: AlertType,
+    pub severity: Severity,
+    /// Plain text, with Slack `*bold*` markup.
+    pub message: String,
+    /// Announces the end of an incident rather than a problem.
+    pub resolved: bool,
+}
+
+impl Firing {
+    fn new(name: &str, alert_type: AlertType, severity: Severity, message: String) -> Firing {
+        Firing {
+            name: String::from(name),
+            alert_type,
+            severity,
+            message,
+            resolved: false,
+        }
+    }
+}
+
+fn replication_error_report(
+    thread: &str,
+    errno: u32,
+    error: &str,
+    timestamp: &str,
+    class: ErrorClass,
+) -> String {
+    let mut report = format!(
+        "Last {} error ({}, errno {}): {}\n",
+        thread, class, errno, error
+    );
+    if !timestamp.is_empty() {
+        report.push_str(&format!("Last {} error timestamp: {}\n", thread, timestamp));
+    }
+
+    report
+}
+
+/// Report a replica that could not be queried, e.g. an unreachable host,
+/// bad credentials or a DNS failure.
+pub async fn unreachable(target: &str, error: &Error) -> Result<Firing, Error> {
+    let beijing_timestamp = utils::time::get_beijing_timestamp_as_rfc2822();
+
+    warn!("💾 Replica {} unreachable: {}", target, error);
+
+    let message = String::new()
+        + &format!("\n\n*Timestamp (Beijing)*: {}\n\n", beijing_timestamp)
+        + &format!("*Warning*: Replica {} is unreachable\n\n", target)
+        + &format!("Replica: {}\n", target)
+        + &format!("Error: {}\n\n", error);
+
+    Ok(Firing::new(
+        "unreachable",
+        AlertType::Unreachable,
+        Severity::Critical,
+        message,
+    ))
+}
+
+/// Report a replica whose alerts keep changing, at `rate` changes per poll;
+/// its alerts are held back until it settles.
+pub async fn flapping(target: &str, rate: f64) -> Result<Firing, Error> {
+    let beijing_timestamp = utils::time::get_beijing_timestamp_as_rfc2822();
+
+    warn!(
+        "💾 Replica {} is flapping ({:.0}% of polls)",
+        target,
+        rate * 100.0
+    );
+
+    let message = String::new()
+        + &format!("\n\n*Timestamp (Beijing)*: {}\n\n", beijing_timestamp)
+        + &format!("*Warning*: Replica {} is flapping\n\n", target)
+        + &format!("Replica: {}\n", target)
+        + &format!(
+            "Alerts changed on {:.0}% of recent polls, further alerts are held back until it settles\n\n",
+            rate * 100.0
+        );
+
+    Ok(Firing::new(
+        "flapping",
+        AlertType::Flapping,
+        Severity::Warning,
+        message,
+    ))
+}
+
+/// Report that a flapping replica settled.
+pub async fn flapping_stopped(target: &str) -> Result<Firing, Error> {
+    let beijing_timestamp = utils::time::get_beijing_timestamp_as_rfc2822();
+
+    info!("💾 Replica {} stopped flapping", target);
+
+    let message = String::new()
+        + &format!("\n\n*Timestamp (Beijing)*: {}\n\n", beijing_timestamp)
+        + &format!("*Resolved*: Replica {} stopped flapping\n\n", target)
+        + &format!("Replica: {}\n", target)
+        + "Alerts still firing are notified as usual from now on\n\n";
+
+    Ok(Firing {
+        resolved: true,
+        ..Firing::new("flapping", AlertType::Flapping, Severity::Warning, message)
+    })
+}
+
+/// Report that the alert `name` on a replica stopped firing.
+pub async fn resolved(target: &str, name: &str, incident: &Incident) -> Result<Firing, Error> {
+    let beijing_timestamp = utils::time::get_beijing_timestamp_as_rfc2822();
+    let peak_lag = match incident.peak_lag {
+        Some(lag) => format!("{} s", lag),
+        None => String::from("unknown"),
+    };
+
+    info!(
+        "💾 Replica {}: {} resolved after {}",
+        target,
+        name,
+        utils::time::format_duration(incident.duration)
+    );
+
+    let message = String::new()
+        + &format!("\n\n*Timestamp (Beijing)*: {}\n\n", beijing_timestamp)
+        + &format!("*Resolved*: {}\n\n", name)
+        + &format!("Replica: {}\n", target)
+        + &format!(
+            "Started: {}\n",
+            utils::time::timestamp_as_rfc2822_from_utc(incident.started_at)
+        )
+        + &format!(
+            "Duration: {}\n",
+            utils::time::format_duration(incident.duration)
+        )
+        + &format!("Peak lag: {}\n\n", peak_lag);
+
+    Ok(Firing {
+        resolved: true,
+        ..Firing::new(name, incident.alert_type, incident.severity, message)
+    })
+}
+
+/// Report on a replica and name everything to alert on: the rules in
+/// `rule_matches`, replication errors, a GTID stall or an unknown lag.
+pub async fn run(
+    target: &str,
+    slave_data: &mut dbslave::DBSlaveStatus,
+    errno_rules: &[ErrnoRule],
+    gtid_stall: Option<&GtidStall>,
+    rule_matches: &[RuleMatch],
+) -> Result<Vec<Firing>, Error> {
     let beijing_timestamp = utils::time::get_beijing_timestamp_as_rfc2822();
-    let behind_master_max: u64 = 300;
 
     // Build status report
     let data = &slave_data;
-    let parsed_seconds_behind_master = data.seconds_behind_master.parse::<u64>().unwrap();
+    let query_alert = match data.lag_seconds() {
+        Some(_) => None,
+        None => Some(QueryAlert::unknown_lag()),
+    };
+    let seconds_behind_master = match data.seconds_behind_master {
+        Some(seconds) => seconds.to_string(),
+        None => String::from("NULL"),
+    };
 
     info!("Current time: {}", Utc::now());
+    info!("💾 Replica: {}", target);
     info!("💾 Slave IO running: {:#?}", data.slave_io_running);
     info!("💾 Slave SQL running: {:#?}", data.slave_sql_running);
     info!(
-        "💾 Slave seconds behind master: {:#?}",
-        data.seconds_behind_master
+        "💾 Slave seconds behind master: {:#?} / heartbeat lag: {:#?}",
+        data.seconds_behind_master, data.heartbeat_lag
     );
 
-    let message = String::new()
-        + &format!("\\n\\n*Timestamp (Beijing)*: {}\\n\\n", beijing_timestamp)
-        + &format!("Master host: {}\\n", data.master_host)
-        + &format!("Master user: {}\\n", data.master_user)
-        + &format!("Slave IO running: {}\\n", data.slave_io_running)
-        + &format!("Slave SQL running: {}\\n", data.slave_sql_running)
-        + &format!("Master log file: {}\\n", data.master_log_file)
-        + &format!("Master log pos: {}\\n", data.read_master_log_pos)
-        + &format!("Relay log file: {}\\n", data.relay_log_file)
-        + &format!("Relay log pos: {}\\n", data.relay_log_pos)
-        + &format!("Relay master log file: {}\\n", data.relay_master_log_file)
-        + &format!(
-            "Slave seconds behind master: {}\\n\\n",
-            data.seconds_behind_master
+    let header = format!("\n\n*Timestamp (Beijing)*: {}\n\n", beijing_timestamp);
+    let mut message = String::new();
+    if let Some(query_alert) = &query_alert {
+        message.push_str(&format!("*Warning*: {}\n\n", query_alert.warning));
+    }
+    message = message
+        + &format!("Replica: {}\n", target)
+        + &format!("Master host: {}\n", data.master_host)
+        + &format!("Master user: {}\n", data.master_user)
+        + &format!("Slave IO running: {}\n", data.slave_io_running)
+        + &format!("Slave SQL running: {}\n", data.slave_sql_running)
+        + &format!("Master log file: {}\n", data.master_log_file)
+        + &format!("Master log pos: {}\n", data.read_master_log_pos)
+        + &format!("Relay log file: {}\n", data.relay_log_file)
+        + &format!("Relay log pos: {}\n", data.relay_log_pos)
+        + &format!("Relay master log file: {}\n", data.relay_master_log_file)
+        + &format!("Slave seconds behind master: {}\n", seconds_behind_master);
+    if let Some(heartbeat_lag) = data.heartbeat_lag {
+        message.push_str(&format!("Heartbeat lag: {}\n", heartbeat_lag));
+    }
+    if !data.server_version.is_empty() {
+        message.push_str(&format!("Server version: {}\n", data.server_version));
+    }
+    if let Some(replay_lag_bytes) = data.replay_lag_bytes {
+        message.push_str(&format!("Replay lag bytes: {}\n", replay_lag_bytes));
+    }
+    if !data.receive_lsn.is_empty() || !data.replay_lsn.is_empty() {
+        message.push_str(&format!(
+            "Receive LSN: {} / Replay LSN: {}\n",
+            data.receive_lsn, data.replay_lsn
+        ));
+    }
+
+    let io_error_class = replication_error::classify(data.last_io_errno, errno_rules);
+    if let Some(class) = io_error_class {
+        message.push_str(&replication_error_report(
+            "IO",
+            data.last_io_errno,
+            &data.last_io_error,
+            &data.last_io_error_timestamp,
+            class,
+        ));
+    }
+
+    let sql_error_class = replication_error::classify(data.last_sql_errno, errno_rules);
+    if let Some(class) = sql_error_class {
+        message.push_str(&replication_error_report(
+            "SQL",
+            data.last_sql_errno,
+            &data.last_sql_error,
+            &data.last_sql_error_timestamp,
+            class,
+        ));
+    }
+
+    if !data.retrieved_gtid_set.is_empty() || !data.executed_gtid_set.is_empty() {
+        message.push_str(&format!("Auto position: {}\n", data.auto_position));
+        message.push_str(&format!(
+            "Retrieved GTID set: {}\n",
+            data.retrieved_gtid_set
+        ));
+        message.push_str(&format!("Executed GTID set: {}\n", data.executed_gtid_set));
+    }
+
+    match gtid_stall {
+        Some(stall) => message.push_str(&format!(
+            "GTID stall: executed set has not advanced for {} poll(s) while retrieved set grew\nMissing GTIDs: {}\n",
+            stall.polls, stall.missing
+        )),
+        None => {
+            let retrieved = GtidSet::parse(&data.retrieved_gtid_set).unwrap_or_default();
+            let executed = GtidSet::parse(&data.executed_gtid_set).unwrap_or_default();
+            let missing = retrieved.subtract(&executed);
+            if !missing.is_empty() {
+                message.push_str(&format!("Missing GTIDs: {}\n", missing));
+            }
+        }
+    }
+    message.push('\n');
+
+    info!(
+        "💾 Last IO errno: {} ({:?}) / Last SQL errno: {} ({:?})",
+        data.last_io_errno, io_error_class, data.last_sql_errno, sql_error_class
+    );
+
+    let report = |summary: &str| format!("{}*Alert*: {}\n\n{}", header, summary, message);
+    let mut firings: Vec<Firing> = rule_matches
+        .iter()
+        .map(|rule_match| {
+            Firing::new(
+                &rule_match.rule.name,
+                AlertType::Replication,
+                rule_match.rule.severity,
+                report(&rule_match.summary()),
+            )
+        })
+        .collect();
+
+    // A replication error alerts by itself, e.g. an IO thread stuck in
+    // `Connecting` on errno 2003, unless its class is configured as `ignore`.
+    // Each class is an alert of its own.
+    let mut error_classes: Vec<ErrorClass> = Vec::new();
+    for class in [io_error_class, sql_error_class].iter().flatten() {
+        if *class != ErrorClass::Ignore && !error_classes.contains(class) {
+            error_classes.push(*class);
+        }
+    }
+    for class in error_classes {
+        let name = class.alert_name();
+        firings.push(Firing::new(
+            &name,
+            AlertType::Replication,
+            class.severity(),
+            report(&format!("{}: {} error", name, class)),
+        ));
+    }
+
+    if let Some(stall) = gtid_stall {
+        firings.push(Firing::new(
+            "gtid_stall",
+            AlertType::Replication,
+            Severity::Warning,
+            report(&format!("gtid_stall: for {} poll(s)", stall.polls)),
+        ));
+    }
+
+    // An unknown lag is raised whenever the lag is NULL, alongside any other
+    // alert, so that it does not resolve while the replica gets worse, e.g.
+    // when its threads stop.
+    if let Some(query_alert) = &query_alert {
+        firings.push(Firing::new(
+            "unknown_lag",
+            query_alert.alert_type,
+            Severity::Warning,
+            report("unknown_lag: lag_seconds is null"),
+        ));
+    }
+
+    info!(
+        "alertable::run(): {} firing {:?}",
+        target,
+        firings
+            .iter()
+            .map(|firing| &firing.name)
+            .collect::<Vec<_>>()
+    );
+    Ok(firings)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dbslave::rules::{self, RuleTracker};
+
+    #[tokio::test]
+    async fn test_null_lag_is_not_caught_up() {
+        let mut status = dbslave::DBSlaveStatus {
+            slave_io_running: String::from("Yes"),
+            slave_sql_running: String::from("Yes"),
+            seconds_behind_master: None,
+            ..dbslave::DBSlaveStatus::default()
+        };
+
+        let firings = run("replica", &mut status, &[], None, &[]).await.unwrap();
+
+        assert_eq!(1, firings.len());
+        assert_eq!(AlertType::UnknownLag, firings[0].alert_type);
+        assert!(firings[0]
+            .message
+            .contains("Slave seconds behind master: NULL"));
+    }
+
+    #[tokio::test]
+    async fn test_rule_matches_are_named_alerts() {
+        let mut status = dbslave::DBSlaveStatus {
+            slave_io_running: String::from("No"),
+            slave_sql_running: String::from("No"),
+            ..dbslave::DBSlaveStatus::default()
+        };
+        let rule_matches = RuleTracker::default()
+            .evaluate(&rules::default_rules(), &status)
+            .unwrap();
+
+        let firings = run("replica", &mut status, &[], None, &rule_matches)
+            .await
+            .unwrap();
+        let names: Vec<&str> = firings.iter().map(|firing| &firing.name[..]).collect();
+
+        assert_eq!(
+            vec!["io_thread_stopped", "sql_thread_stopped", "unknown_lag"],
+            names
         );
+        assert!(firings[..2]
+            .iter()
+            .all(|firing| firing.alert_type == AlertType::Replication));
+        assert!(firings[0]
+            .message
+            .contains("*Alert*: io_thread_stopped: slave_io_running == No"));
+    }
+
+    #[tokio::test]
+    async fn test_heartbeat_lag_replaces_reported_lag() {
+        let mut status = dbslave::DBSlaveStatus {
+            slave_io_running: String::from("Yes"),
+            slave_sql_running: String::from("Yes"),
+            seconds_behind_master: Some(0),
+            heartbeat_lag: Some(900),
+            ..dbslave::DBSlaveStatus::default()
+        };
+        let rule_matches = RuleTracker::default()
+            .evaluate(&rules::default_rules(), &status)
+            .unwrap();
+
+        let firings = run("replica", &mut status, &[], None, &rule_matches)
+            .await
+            .unwrap();
 
-    if data.slave_io_running == String::from("No")
-        || data.slave_sql_running == String::from("No")
-        || parsed_seconds_behind_master > behind_master_max
-    {
-        alertable = true;
+        assert_eq!(1, firings.len());
+        assert_eq!("replication_lag", firings[0].name);
+        assert!(firings[0].message.contains("Heartbeat lag: 900"));
     }
 
-    info!("alertable::run(): notify_now? {}", alertable);
-    Ok((alertable, message))
+    #[tokio::test]
+    async fn test_error_classes_are_separate_alerts() {
+        let mut status = dbslave::DBSlaveStatus {
+            slave_io_running: String::from("Connecting"),
+            slave_sql_running: String::from("No"),
+            seconds_behind_master: Some(0),
+            last_io_errno: 2003,
+            last_sql_errno: 1062,
+            ..dbslave::DBSlaveStatus::default()
+        };
+
+        let firings = run("replica", &mut status, &[], None, &[]).await.unwrap();
+        let alerts: Vec<(&str, Severity)> = firings
+            .iter()
+            .map(|firing| (&firing.name[..], firing.severity))
+            .collect();
+
+        assert_eq!(
+            vec![
+                ("replication_error_network", Severity::Warning),
+                ("replication_error_data", Severity::Critical),
+            ],
+            alerts
+        );
+    }
+
+    #[tokio::test]
+    async fn test_unknown_lag_stays_with_other_alerts() {
+        let mut status = dbslave::DBSlaveStatus {
+            slave_io_running: String::from("Yes"),
+            slave_sql_running: String::from("Yes"),
+            seconds_behind_master: None,
+            ..dbslave::DBSlaveStatus::default()
+        };
+        let firings = run("replica", &mut status, &[], None, &[]).await.unwrap();
+        assert_eq!("unknown_lag", firings[0].name);
+
+        status.slave_sql_running = String::from("No");
+        status.last_sql_errno = 1062;
+        let firings = run("replica", &mut status, &[], None, &[]).await.unwrap();
+        let names: Vec<&str> = firings.iter().map(|firing| &firing.name[..]).collect();
+
+        assert_eq!(vec!["replication_error_data", "unknown_lag"], names);
+    }
 }
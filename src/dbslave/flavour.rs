@@ -0,0 +1,171 @@
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Flavour {
+    Mysql,
+    MariaDb,
+    Percona,
+}
+
+impl fmt::Display for Flavour {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Flavour::Mysql => "MySQL",
+            Flavour::MariaDb => "MariaDB",
+            Flavour::Percona => "Percona Server",
+        };
+
+        write!(f, "{}", name)
+    }
+}
+
+/// Column names of the replication status statement.
+///
+/// MySQL 8.0.22 renamed `SHOW SLAVE STATUS` to `SHOW REPLICA STATUS` and the
+/// `Master`/`Slave` columns to `Source`/`Replica`; the old names are removed
+/// in later releases.
+#[derive(Debug, PartialEq)]
+pub struct StatusColumns {
+    pub statement: &'static str,
+    pub master_host: &'static str,
+    pub master_user: &'static str,
+    pub slave_io_running: &'static str,
+    pub slave_sql_running: &'static str,
+    pub master_log_file: &'static str,
+    pub read_master_log_pos: &'static str,
+    pub relay_log_file: &'static str,
+    pub relay_log_pos: &'static str,
+    pub relay_master_log_file: &'static str,
+    pub seconds_behind_master: &'static str,
+}
+
+pub static SLAVE_COLUMNS: StatusColumns = StatusColumns {
+    statement: "SHOW SLAVE STATUS",
+    master_host: "Master_Host",
+    master_user: "Master_User",
+    slave_io_running: "Slave_IO_Running",
+    slave_sql_running: "Slave_SQL_Running",
+    master_log_file: "Master_Log_File",
+    read_master_log_pos: "Read_Master_Log_Pos",
+    relay_log_file: "Relay_Log_File",
+    relay_log_pos: "Relay_Log_Pos",
+    relay_master_log_file: "Relay_Master_Log_File",
+    seconds_behind_master: "Seconds_Behind_Master",
+};
+
+pub static REPLICA_COLUMNS: StatusColumns = StatusColumns {
+    statement: "SHOW REPLICA STATUS",
+    master_host: "Source_Host",
+    master_user: "Source_User",
+    slave_io_running: "Replica_IO_Running",
+    slave_sql_running: "Replica_SQL_Running",
+    master_log_file: "Source_Log_File",
+    read_master_log_pos: "Read_Source_Log_Pos",
+    relay_log_file: "Relay_Log_File",
+    relay_log_pos: "Relay_Log_Pos",
+    relay_master_log_file: "Relay_Source_Log_File",
+    seconds_behind_master: "Seconds_Behind_Source",
+};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ServerVersion {
+    pub flavour: Flavour,
+    pub major: u32,
+    pub minor: u32,
+    pub patch: u32,
+}
+
+impl fmt::Display for ServerVersion {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} {}.{}.{}",
+            self.flavour, self.major, self.minor, self.patch
+        )
+    }
+}
+
+impl ServerVersion {
+    /// Parse the results of `SELECT VERSION(), @@version_comment`, e.g.
+    /// `8.0.23-14` / `Percona Server (GPL), Release 14, Revision 3558242`.
+    pub fn parse(version: &str, version_comment: &str) -> ServerVersion {
+        let flavour = if version.contains("MariaDB") || version_comment.contains("MariaDB") {
+            Flavour::MariaDb
+        } else if version_comment.contains("Percona") {
+            Flavour::Percona
+        } else {
+            Flavour::Mysql
+        };
+
+        let numbers: Vec<u32> = version
+            .split(|c: char| !c.is_ascii_digit() && c != '.')
+            .next()
+            .unwrap_or("")
+            .split('.')
+            .map(|part| part.parse::<u32>().unwrap_or(0))
+            .collect();
+        let part = |index: usize| numbers.get(index).cloned().unwrap_or(0);
+
+        ServerVersion {
+            flavour,
+            major: part(0),
+            minor: part(1),
+            patch: part(2),
+        }
+    }
+
+    /// Select the column set understood by this server.
+    ///
+    /// MariaDB accepts `SHOW REPLICA STATUS` from 10.5.1 but still returns the
+    /// `Slave_*` columns, so it always uses the original names.
+    pub fn status_columns(&self) -> &'static StatusColumns {
+        match self.flavour {
+            Flavour::MariaDb => &SLAVE_COLUMNS,
+            Flavour::Mysql | Flavour::Percona => {
+                if (self.major, self.minor, self.patch) >= (8, 0, 22) {
+                    &REPLICA_COLUMNS
+                } else {
+                    &SLAVE_COLUMNS
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_flavours() {
+        let mysql = ServerVersion::parse("5.7.30-log", "MySQL Community Server (GPL)");
+        assert_eq!(Flavour::Mysql, mysql.flavour);
+        assert_eq!((5, 7, 30), (mysql.major, mysql.minor, mysql.patch));
+
+        let mariadb = ServerVersion::parse("10.5.8-MariaDB-1:10.5.8+maria~focal", "");
+        assert_eq!(Flavour::MariaDb, mariadb.flavour);
+        assert_eq!((10, 5, 8), (mariadb.major, mariadb.minor, mariadb.patch));
+
+        let percona = ServerVersion::parse("8.0.23-14", "Percona Server (GPL), Release 14");
+        assert_eq!(Flavour::Percona, percona.flavour);
+        assert_eq!((8, 0, 23), (percona.major, percona.minor, percona.patch));
+    }
+
+    #[test]
+    fn test_status_columns() {
+        let columns = |version: &str, comment: &str| {
+            ServerVersion::parse(version, comment)
+                .status_columns()
+                .statement
+        };
+
+        assert_eq!("SHOW SLAVE STATUS", columns("8.0.21", ""));
+        assert_eq!("SHOW REPLICA STATUS", columns("8.0.22", ""));
+        assert_eq!("SHOW REPLICA STATUS", columns("8.4.0", ""));
+        assert_eq!(
+            "SHOW REPLICA STATUS",
+            columns("8.0.25-15", "Percona Server")
+        );
+        assert_eq!("SHOW SLAVE STATUS", columns("10.6.4-MariaDB", ""));
+    }
+}
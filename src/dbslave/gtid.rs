@@ -0,0 +1,327 @@
+use crate::dbslave::DBSlaveStatus;
+use crate::errors::Error;
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// A MySQL GTID set such as `3E11FA47-71CA-11E1-9E33-C80AA9429562:1-5:7`.
+///
+/// Intervals are inclusive, sorted and merged per source UUID (and tag, for
+/// MySQL 8.3+ tagged GTIDs).
+///
+/// A MariaDB GTID position such as `0-1-100` (domain, server id, sequence
+/// number) is read as transactions 1 to 100 of domain `0`, and shown as
+/// `0:1-100`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct GtidSet {
+    intervals: BTreeMap<String, Vec<(u64, u64)>>,
+}
+
+fn merge(intervals: &mut Vec<(u64, u64)>) {
+    intervals.sort();
+
+    let mut merged: Vec<(u64, u64)> = Vec::with_capacity(intervals.len());
+    for &(start, end) in intervals.iter() {
+        match merged.last_mut() {
+            Some(last) if start <= last.1.saturating_add(1) => last.1 = last.1.max(end),
+            _ => merged.push((start, end)),
+        }
+    }
+
+    *intervals = merged;
+}
+
+/// The domain and sequence number of a MariaDB GTID, `domain-server-seq`.
+fn mariadb_position(member: &str) -> Option<(String, u64)> {
+    let parts: Vec<&str> = member.split('-').collect();
+    if parts.len() != 3 || parts[1].parse::<u64>().is_err() {
+        return None;
+    }
+
+    Some((
+        parts[0].parse::<u32>().ok()?.to_string(),
+        parts[2].parse::<u64>().ok()?,
+    ))
+}
+
+impl GtidSet {
+    /// Parse `Retrieved_Gtid_Set` / `Executed_Gtid_Set`, which MySQL wraps
+    /// with newlines after each comma, or MariaDB's `Gtid_IO_Pos` /
+    /// `@@gtid_slave_pos`.
+    pub fn parse(text: &str) -> Result<GtidSet, Error> {
+        let mut set = GtidSet::default();
+        let compact: String = text.chars().filter(|c| !c.is_whitespace()).collect();
+
+        for member in compact.split(',').filter(|member| !member.is_empty()) {
+            if let Some((domain, sequence)) = mariadb_position(member) {
+                // Nothing has been applied in a domain at sequence 0.
+                if sequence == 0 {
+                    continue;
+                }
+                set.intervals
+                    .entry(domain)
+                    .or_insert_with(Vec::new)
+                    .push((1, sequence));
+                continue;
+            }
+
+            let mut parts = member.split(':');
+            let uuid = parts.next().unwrap_or("").to_lowercase();
+            let mut source = uuid.clone();
+
+            for part in parts {
+                let bounds: Vec<&str> = part.splitn(2, '-').collect();
+                let start = match bounds[0].parse::<u64>() {
+                    Ok(start) => start,
+                    Err(_) => {
+                        // A tag applies to the intervals that follow it.
+                        source = format!("{}:{}", uuid, part.to_lowercase());
+                        continue;
+                    }
+                };
+                let end = match bounds.get(1) {
+                    Some(end) => end.parse::<u64>().map_err(|_| {
+                        Error::Internal(format!("Invalid GTID interval: {}", member))
+                    })?,
+                    None => start,
+                };
+
+                set.intervals
+                    .entry(source.clone())
+                    .or_insert_with(Vec::new)
+                    .push((start, end.max(start)));
+            }
+        }
+
+        for intervals in set.intervals.values_mut() {
+            merge(intervals);
+        }
+
+        Ok(set)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.intervals
+            .values()
+            .all(|intervals| intervals.is_empty())
+    }
+
+    /// Number of transactions in the set.
+    pub fn count(&self) -> u64 {
+        self.intervals
+            .values()
+            .flatten()
+            .map(|(start, end)| end - start + 1)
+            .sum()
+    }
+
+    /// Transactions in `self` which are not in `other`.
+    pub fn subtract(&self, other: &GtidSet) -> GtidSet {
+        let mut result = GtidSet::default();
+
+        for (source, intervals) in &self.intervals {
+            let removed = other.intervals.get(source).cloned().unwrap_or_default();
+            let mut remaining = Vec::new();
+
+            for &(start, end) in intervals {
+                let mut cursor = start;
+                for &(remove_start, remove_end) in &removed {
+                    if remove_end < cursor || remove_start > end {
+                        continue;
+                    }
+                    if remove_start > cursor {
+                        remaining.push((cursor, remove_start - 1));
+                    }
+                    cursor = remove_end.saturating_add(1);
+                    if cursor > end {
+                        break;
+                    }
+                }
+                if cursor <= end && cursor >= start {
+                    remaining.push((cursor, end));
+                }
+            }
+
+            if !remaining.is_empty() {
+                result.intervals.insert(source.clone(), remaining);
+            }
+        }
+
+        result
+    }
+}
+
+impl fmt::Display for GtidSet {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let members: Vec<String> = self
+            .intervals
+            .iter()
+            .filter(|(_, intervals)| !intervals.is_empty())
+            .map(|(source, intervals)| {
+                let ranges: Vec<String> = intervals
+                    .iter()
+                    .map(|&(start, end)| {
+                        if start == end {
+                            start.to_string()
+                        } else {
+                            format!("{}-{}", start, end)
+                        }
+                    })
+                    .collect();
+
+                format!("{}:{}", source, ranges.join(":"))
+            })
+            .collect();
+
+        write!(f, "{}", members.join(","))
+    }
+}
+
+/// The executed set stopped advancing while the retrieved set kept growing.
+#[derive(Debug, Clone)]
+pub struct GtidStall {
+    /// Consecutive polls over which the stall has been observed.
+    pub polls: u64,
+    pub missing: GtidSet,
+}
+
+/// Tracks GTID progress of a replica between polls.
+#[derive(Debug, Default)]
+pub struct GtidProgress {
+    previous: Option<(GtidSet, GtidSet)>,
+    stalled_polls: u64,
+}
+
+impl GtidProgress {
+    /// Record the GTID sets of the latest poll and report a stall, if any.
+    ///
+    /// Replicas not using GTIDs never stall.
+    pub fn update(&mut self, status: &DBSlaveStatus) -> Option<GtidStall> {
+        let retrieved = GtidSet::parse(&status.retrieved_gtid_set).unwrap_or_default();
+        let executed = GtidSet::parse(&status.executed_gtid_set).unwrap_or_default();
+
+        if retrieved.is_empty() {
+            self.previous = None;
+            self.stalled_polls = 0;
+            return None;
+        }
+
+        let stalled = match &self.previous {
+            Some((previous_retrieved, previous_executed)) => {
+                retrieved.count() > previous_retrieved.count()
+                    && executed.count() <= previous_executed.count()
+            }
+            None => false,
+        };
+
+        self.stalled_polls = if stalled { self.stalled_polls + 1 } else { 0 };
+        let missing = retrieved.subtract(&executed);
+        self.previous = Some((retrieved, executed));
+
+        if self.stalled_polls > 0 {
+            Some(GtidStall {
+                polls: self.stalled_polls,
+                missing,
+            })
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const UUID: &str = "3e11fa47-71ca-11e1-9e33-c80aa9429562";
+
+    #[test]
+    fn test_parse_and_display() {
+        let set = GtidSet::parse(
+            "3E11FA47-71CA-11E1-9E33-C80AA9429562:1-5:7,\n4e11fa47-71ca-11e1-9e33-c80aa9429562:1-3",
+        )
+        .unwrap();
+
+        assert_eq!(9, set.count());
+        assert_eq!(
+            format!("{}:1-5:7,4e11fa47-71ca-11e1-9e33-c80aa9429562:1-3", UUID),
+            set.to_string()
+        );
+    }
+
+    #[test]
+    fn test_parse_merges_adjacent_intervals() {
+        let set = GtidSet::parse(&format!("{}:1-3:4-6:10", UUID)).unwrap();
+
+        assert_eq!(format!("{}:1-6:10", UUID), set.to_string());
+        assert_eq!(7, set.count());
+    }
+
+    #[test]
+    fn test_parse_tagged() {
+        let set = GtidSet::parse(&format!("{}:1-5:batch:1-2", UUID)).unwrap();
+
+        assert_eq!(7, set.count());
+        assert_eq!(format!("{}:1-5,{}:batch:1-2", UUID, UUID), set.to_string());
+    }
+
+    #[test]
+    fn test_parse_mariadb_positions() {
+        let set = GtidSet::parse("0-1-100,\n1-2-5,2-1-0").unwrap();
+
+        assert_eq!(105, set.count());
+        assert_eq!("0:1-100,1:1-5", set.to_string());
+        assert_eq!(
+            "0:91-100",
+            set.subtract(&GtidSet::parse("0-1-90,1-2-5").unwrap())
+                .to_string()
+        );
+    }
+
+    #[test]
+    fn test_subtract() {
+        let retrieved = GtidSet::parse(&format!("{}:1-100", UUID)).unwrap();
+        let executed = GtidSet::parse(&format!("{}:1-40:45-50", UUID)).unwrap();
+
+        assert_eq!(
+            format!("{}:41-44:51-100", UUID),
+            retrieved.subtract(&executed).to_string()
+        );
+        assert!(executed.subtract(&retrieved).is_empty());
+    }
+
+    #[test]
+    fn test_progress_detects_stall() {
+        let mut progress = GtidProgress::default();
+        let mut status = DBSlaveStatus {
+            retrieved_gtid_set: format!("{}:1-10", UUID),
+            executed_gtid_set: format!("{}:1-8", UUID),
+            ..DBSlaveStatus::default()
+        };
+        assert!(progress.update(&status).is_none());
+
+        status.retrieved_gtid_set = format!("{}:1-20", UUID);
+        let stall = progress.update(&status).unwrap();
+        assert_eq!(1, stall.polls);
+        assert_eq!(format!("{}:9-20", UUID), stall.missing.to_string());
+
+        status.executed_gtid_set = format!("{}:1-20", UUID);
+        status.retrieved_gtid_set = format!("{}:1-21", UUID);
+        assert!(progress.update(&status).is_none());
+    }
+
+    #[test]
+    fn test_progress_detects_mariadb_stall() {
+        let mut progress = GtidProgress::default();
+        let mut status = DBSlaveStatus {
+            retrieved_gtid_set: String::from("0-1-10"),
+            executed_gtid_set: String::from("0-1-8"),
+            ..DBSlaveStatus::default()
+        };
+        assert!(progress.update(&status).is_none());
+
+        status.retrieved_gtid_set = String::from("0-1-20");
+        let stall = progress.update(&status).unwrap();
+        assert_eq!(1, stall.polls);
+        assert_eq!("0:9-20", stall.missing.to_string());
+    }
+}
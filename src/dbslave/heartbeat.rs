@@ -0,0 +1,131 @@
+use crate::errors::Error;
+use serde::Deserialize;
+
+fn default_table() -> String {
+    String::from("percona.heartbeat")
+}
+
+fn default_column() -> String {
+    String::from("ts")
+}
+
+fn default_server_id_column() -> String {
+    String::from("server_id")
+}
+
+/// A pt-heartbeat style table, written on the master and replicated to the
+/// replica, used to measure lag in place of `Seconds_Behind_Master`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct HeartbeatConfig {
+    #[serde(default = "default_table")]
+    pub table: String,
+    #[serde(default = "default_column")]
+    pub column: String,
+    /// Only read rows written by this master, as with
+    /// `pt-heartbeat --master-server-id`.
+    pub server_id: Option<u32>,
+    /// The column holding the master's server id, for `server_id`.
+    #[serde(default = "default_server_id_column")]
+    pub server_id_column: String,
+    /// Whether the master writes timestamps in UTC (`pt-heartbeat --utc`).
+    /// pt-heartbeat writes its system time zone by default, so this is off
+    /// unless configured.
+    #[serde(default)]
+    pub utc: bool,
+}
+
+/// Quote a possibly schema-qualified identifier such as `percona.heartbeat`.
+fn quote_identifier(identifier: &str) -> Result<String, Error> {
+    let parts: Vec<&str> = identifier.split('.').collect();
+    let valid = parts.iter().all(|part| {
+        !part.is_empty()
+            && part
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '$')
+    });
+
+    if !valid {
+        return Err(Error::Internal(format!(
+            "Invalid heartbeat identifier in config: {}",
+            identifier
+        )));
+    }
+
+    let quoted: Vec<String> = parts.iter().map(|part| format!("`{}`", part)).collect();
+    Ok(quoted.join("."))
+}
+
+impl HeartbeatConfig {
+    /// The query returning the lag in seconds of the newest heartbeat,
+    /// measured against the replica's own clock.
+    pub fn lag_query(&self) -> Result<String, Error> {
+        let table = quote_identifier(&self.table)?;
+        let column = quote_identifier(&self.column)?;
+        let now = if self.utc { "UTC_TIMESTAMP()" } else { "NOW()" };
+        let filter = match self.server_id {
+            Some(server_id) => format!(
+                " WHERE {} = {}",
+                quote_identifier(&self.server_id_column)?,
+                server_id
+            ),
+            None => String::new(),
+        };
+
+        Ok(format!(
+            "SELECT CAST(GREATEST(0, TIMESTAMPDIFF(SECOND, MAX({column}), {now})) AS SIGNED) \
+             AS heartbeat_lag FROM {table}{filter}",
+            column = column,
+            now = now,
+            table = table,
+            filter = filter
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lag_query() {
+        let config = HeartbeatConfig {
+            table: default_table(),
+            column: default_column(),
+            server_id: Some(1),
+            server_id_column: default_server_id_column(),
+            utc: true,
+        };
+
+        assert_eq!(
+            "SELECT CAST(GREATEST(0, TIMESTAMPDIFF(SECOND, MAX(`ts`), UTC_TIMESTAMP())) AS SIGNED) \
+             AS heartbeat_lag FROM `percona`.`heartbeat` WHERE `server_id` = 1",
+            config.lag_query().unwrap()
+        );
+    }
+
+    #[test]
+    fn test_lag_query_rejects_invalid_identifiers() {
+        let config = HeartbeatConfig {
+            table: String::from("heartbeat; DROP TABLE users"),
+            column: default_column(),
+            server_id: None,
+            server_id_column: default_server_id_column(),
+            utc: true,
+        };
+
+        assert!(config.lag_query().is_err());
+    }
+
+    #[test]
+    fn test_defaults_to_local_time() {
+        let config: HeartbeatConfig =
+            serde_json::from_str(r#"{"server_id": 7, "server_id_column": "master_id"}"#).unwrap();
+
+        assert!(!config.utc);
+        assert_eq!(
+            "SELECT CAST(GREATEST(0, TIMESTAMPDIFF(SECOND, MAX(`ts`), NOW())) AS SIGNED) \
+             AS heartbeat_lag FROM `percona`.`heartbeat` WHERE `master_id` = 7",
+            config.lag_query().unwrap()
+        );
+    }
+}
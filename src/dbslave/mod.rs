@@ -1,25 +1,141 @@
-use crate::alerts;
-use crate::monitor::{Alert, SentAlerts};
 use crate::configure;
 use crate::errors::Error;
 use crate::sqlx::Cursor;
 use crate::sqlx::Row;
 use async_trait::async_trait;
-use std::collections::VecDeque;
-use std::panic;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
 
 pub mod alertable;
+pub mod flavour;
+pub mod gtid;
+pub mod heartbeat;
+pub mod pool;
+pub mod postgres;
+pub mod replication_error;
+pub mod rules;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ConnectorKind {
+    #[default]
+    Mysql,
+    Postgres,
+}
+
+/// A named replica to be watched, as listed under `replicas` in the config.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ReplicaTarget {
+    pub name: String,
+    pub url: String,
+    #[serde(default)]
+    pub connector: ConnectorKind,
+    /// PostgreSQL only: the primary, queried for `pg_stat_replication`.
+    pub primary_url: Option<String>,
+    /// PostgreSQL only: the standby's `application_name` on the primary.
+    /// Defaults to the target name.
+    pub application_name: Option<String>,
+    /// MySQL only: measure lag from a heartbeat table instead of
+    /// `Seconds_Behind_Master`.
+    pub heartbeat: Option<heartbeat::HeartbeatConfig>,
+    #[serde(default)]
+    pub pool: pool::PoolConfig,
+    /// Alert rules of this replica, replacing global rules of the same name.
+    #[serde(default)]
+    pub rules: Vec<rules::Rule>,
+    /// Label of the cluster the replica belongs to, for grouping alerts.
+    pub cluster: Option<String>,
+}
+
+/// Load the replica targets from config.
+///
+/// Falls back to a single target named `default` built from `mysql_url`
+/// when no `replicas` list is configured.
+pub fn targets() -> Result<Vec<ReplicaTarget>, Error> {
+    let targets = match configure::fetch_section::<Vec<ReplicaTarget>>("replicas")? {
+        Some(targets) => targets,
+        None => vec![ReplicaTarget {
+            name: String::from("default"),
+            url: configure::fetch::<String>(String::from("mysql_url"))?,
+            connector: ConnectorKind::Mysql,
+            primary_url: None,
+            application_name: None,
+            heartbeat: None,
+            pool: pool::PoolConfig::default(),
+            rules: Vec::new(),
+            cluster: None,
+        }],
+    };
+
+    let mut names = HashSet::new();
+    for target in &targets {
+        if !names.insert(&target.name) {
+            return Err(Error::Internal(format!(
+                "Duplicate replica name in config: {}",
+                target.name
+            )));
+        }
+    }
+
+    Ok(targets)
+}
 
 #[derive(Debug)]
-pub struct ConnectorMysql;
+pub struct ConnectorMysql {
+    pub pool: pool::LazyPool<sqlx::MySqlConnection>,
+    pub heartbeat: Option<heartbeat::HeartbeatConfig>,
+}
+
+impl ConnectorMysql {
+    pub fn new(target: &ReplicaTarget) -> ConnectorMysql {
+        ConnectorMysql {
+            pool: pool::LazyPool::new(&target.url, &target.pool),
+            heartbeat: target.heartbeat.clone(),
+        }
+    }
+}
 
 #[derive(Debug)]
-pub struct ConnectorPostgres;
+pub struct ConnectorPostgres {
+    pub standby: pool::LazyPool<sqlx::PgConnection>,
+    pub primary: Option<pool::LazyPool<sqlx::PgConnection>>,
+    pub application_name: String,
+}
+
+impl ConnectorPostgres {
+    pub fn new(target: &ReplicaTarget) -> ConnectorPostgres {
+        ConnectorPostgres {
+            standby: pool::LazyPool::new(&target.url, &target.pool),
+            primary: target
+                .primary_url
+                .as_ref()
+                .map(|url| pool::LazyPool::new(url, &target.pool)),
+            application_name: target
+                .application_name
+                .clone()
+                .unwrap_or_else(|| target.name.clone()),
+        }
+    }
+}
 
+/// The connector of a replica target, kept for the lifetime of the watch so
+/// that its connection pools are reused across polls.
 #[derive(Debug)]
-struct Connection;
+pub enum Connector {
+    Mysql(ConnectorMysql),
+    Postgres(ConnectorPostgres),
+}
+
+impl Connector {
+    pub fn new(target: &ReplicaTarget) -> Connector {
+        match target.connector {
+            ConnectorKind::Mysql => Connector::Mysql(ConnectorMysql::new(target)),
+            ConnectorKind::Postgres => Connector::Postgres(ConnectorPostgres::new(target)),
+        }
+    }
+}
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default, Serialize)]
 pub struct DBSlaveStatus {
     pub master_host: String,
     pub master_user: String,
@@ -30,29 +146,41 @@ pub struct DBSlaveStatus {
     pub relay_log_file: String,
     pub relay_log_pos: u64,
     pub relay_master_log_file: String,
-    pub seconds_behind_master: String,
+    /// `None` when the server reports NULL, i.e. the lag is unknown.
+    pub seconds_behind_master: Option<u64>,
+    /// Lag measured from a heartbeat table, when one is configured.
+    pub heartbeat_lag: Option<u64>,
+    /// Replay lag in bytes of WAL; only reported by PostgreSQL standbys.
+    pub replay_lag_bytes: Option<u64>,
+    /// Last WAL position received and replayed, e.g. `0/3000148`; only
+    /// reported by PostgreSQL standbys.
+    pub receive_lsn: String,
+    pub replay_lsn: String,
+    /// Flavour and version of the server, e.g. `MySQL 8.0.23`.
+    pub server_version: String,
+    pub last_io_errno: u32,
+    pub last_io_error: String,
+    pub last_io_error_timestamp: String,
+    pub last_sql_errno: u32,
+    pub last_sql_error: String,
+    pub last_sql_error_timestamp: String,
+    pub retrieved_gtid_set: String,
+    pub executed_gtid_set: String,
+    pub auto_position: bool,
 }
 
-impl std::convert::AsRef<DBSlaveStatus> for DBSlaveStatus {
-    fn as_ref(&self) -> &DBSlaveStatus {
-        self
+impl DBSlaveStatus {
+    /// Replication lag in seconds, preferring the heartbeat measurement over
+    /// the server-reported value, which is unreliable with multi-threaded
+    /// and chained replication.
+    pub fn lag_seconds(&self) -> Option<u64> {
+        self.heartbeat_lag.or(self.seconds_behind_master)
     }
 }
 
-impl Default for DBSlaveStatus {
-    fn default() -> Self {
-        Self {
-            master_host: String::new(),
-            master_user: String::new(),
-            slave_io_running: String::new(),
-            slave_sql_running: String::new(),
-            master_log_file: String::new(),
-            read_master_log_pos: 0,
-            relay_log_file: String::new(),
-            relay_log_pos: 0,
-            relay_master_log_file: String::new(),
-            seconds_behind_master: String::from("0"),
-        }
+impl std::convert::AsRef<DBSlaveStatus> for DBSlaveStatus {
+    fn as_ref(&self) -> &DBSlaveStatus {
+        self
     }
 }
 
@@ -61,65 +189,158 @@ pub trait Fetch<T> {
     async fn fetch_dbslave_status<'a>(&'a self) -> T;
 }
 
-#[async_trait]
-impl Fetch<Result<DBSlaveStatus, Error>> for ConnectorMysql {
-    async fn fetch_dbslave_status(&self) -> Result<DBSlaveStatus, Error> {
-        let mysql_url: String = configure::fetch::<String>(String::from("mysql_url")).unwrap();
-        let pool = sqlx::MySqlPool::builder().build(&mysql_url[..]).await?;
-        // println!("Pool: {:#?}", pool);
+/// Read an integer column such as an errno, whose type differs between server
+/// versions.
+fn get_u32(row: &sqlx::mysql::MySqlRow, column: &str) -> u32 {
+    if let Ok(value) = row.try_get::<u32, &str>(column) {
+        return value;
+    }
+
+    if let Ok(value) = row.try_get::<i32, &str>(column) {
+        return value.max(0) as u32;
+    }
+
+    match row.try_get::<String, &str>(column) {
+        Ok(value) => value.parse::<u32>().unwrap_or(0),
+        Err(_) => 0,
+    }
+}
+
+/// Read a nullable integer column, returning `None` for NULL.
+fn get_optional_u64(row: &sqlx::mysql::MySqlRow, column: &str) -> Option<u64> {
+    if let Ok(value) = row.try_get::<Option<u64>, &str>(column) {
+        return value;
+    }
+
+    if let Ok(value) = row.try_get::<Option<i64>, &str>(column) {
+        return value.map(|value| value.max(0) as u64);
+    }
+
+    match row.try_get::<Option<String>, &str>(column) {
+        Ok(value) => value.and_then(|value| value.parse::<u64>().ok()),
+        Err(_) => None,
+    }
+}
+
+/// Read an optional text column, such as the error timestamps that only
+/// exist from MySQL 5.6 onwards.
+fn get_text(row: &sqlx::mysql::MySqlRow, column: &str) -> String {
+    match row.try_get::<Option<String>, &str>(column) {
+        Ok(value) => value.unwrap_or_default(),
+        Err(_) => String::new(),
+    }
+}
+
+async fn query_mysql_status(
+    pool: sqlx::MySqlPool,
+    heartbeat: Option<&heartbeat::HeartbeatConfig>,
+) -> Result<DBSlaveStatus, Error> {
+    let sql = "SELECT VERSION() AS version, @@version_comment AS version_comment";
+    let mut cursor = sqlx::query(sql).fetch(&pool);
+    let server_version = match cursor.next().await? {
+        Some(row) => flavour::ServerVersion::parse(
+            &get_text(&row, "version"),
+            &get_text(&row, "version_comment"),
+        ),
+        None => flavour::ServerVersion::parse("", ""),
+    };
+    drop(cursor);
+
+    let columns = server_version.status_columns();
+    info!("Server version: {} / {}", server_version, columns.statement);
+
+    // MariaDB keeps its GTID positions in `Gtid_IO_Pos` and
+    // `@@gtid_slave_pos` rather than MySQL's GTID set columns.
+    let mariadb = server_version.flavour == flavour::Flavour::MariaDb;
 
-        let sql = "SHOW SLAVE STATUS";
+    let mut cursor = sqlx::query(columns.statement).fetch(&pool);
+    let mut result: DBSlaveStatus = DBSlaveStatus::default();
+
+    while let Some(row) = cursor.next().await? {
+        let data = DBSlaveStatus {
+            master_host: row.get(columns.master_host),
+            master_user: row.get(columns.master_user),
+            slave_io_running: row.get(columns.slave_io_running),
+            slave_sql_running: row.get(columns.slave_sql_running),
+            master_log_file: row.get(columns.master_log_file),
+            read_master_log_pos: row.get(columns.read_master_log_pos),
+            relay_log_file: row.get(columns.relay_log_file),
+            relay_log_pos: row.get(columns.relay_log_pos),
+            relay_master_log_file: row.get(columns.relay_master_log_file),
+            // When DB Slave is disabled with `STOP SLAVE;` it returns
+            // Seconds_Behind_Master: NULL.
+            seconds_behind_master: get_optional_u64(&row, columns.seconds_behind_master),
+            heartbeat_lag: None,
+            replay_lag_bytes: None,
+            receive_lsn: String::new(),
+            replay_lsn: String::new(),
+            server_version: server_version.to_string(),
+            last_io_errno: get_u32(&row, "Last_IO_Errno"),
+            last_io_error: get_text(&row, "Last_IO_Error"),
+            last_io_error_timestamp: get_text(&row, "Last_IO_Error_Timestamp"),
+            last_sql_errno: get_u32(&row, "Last_SQL_Errno"),
+            last_sql_error: get_text(&row, "Last_SQL_Error"),
+            last_sql_error_timestamp: get_text(&row, "Last_SQL_Error_Timestamp"),
+            retrieved_gtid_set: if mariadb {
+                get_text(&row, "Gtid_IO_Pos")
+            } else {
+                get_text(&row, "Retrieved_Gtid_Set")
+            },
+            executed_gtid_set: if mariadb {
+                String::new()
+            } else {
+                get_text(&row, "Executed_Gtid_Set")
+            },
+            auto_position: if mariadb {
+                !["", "No"].contains(&get_text(&row, "Using_Gtid").as_str())
+            } else {
+                get_u32(&row, "Auto_Position") == 1
+            },
+        };
+
+        result = data;
+    }
+    drop(cursor);
+
+    if mariadb {
+        let sql = "SELECT @@gtid_slave_pos AS gtid_slave_pos";
         let mut cursor = sqlx::query(sql).fetch(&pool);
-        let mut result: DBSlaveStatus = DBSlaveStatus::default();
-
-        while let Some(row) = cursor.next().await? {
-            let mut alert_state: bool = false;
-            let seconds_behind_master: String = String::from("0");
-            let read_behind_master = match row.try_get::<String, &str>("Seconds_Behind_Master") {
-                Ok(val) => val,
-                _ => {
-                    // When DB Slave is disabled with `STOP SLAVE;` it returns
-                    // Seconds_Behind_Master: NULL and sqlx raises a
-                    // `UnexpectedNullError`
-
-                    // TODO: handle this as a `QueryAlert`
-                    let _ = alerts::QueryAlert {
-                        warning: String::from("DB Slave returned `Seconds_Behind_Master: NULL`"),
-                    };
-                    alert_state = true;
-
-                    seconds_behind_master
-                }
-            };
-
-            let data = DBSlaveStatus {
-                master_host: row.get("Master_Host"),
-                master_user: row.get("Master_User"),
-                slave_io_running: row.get("Slave_IO_Running"),
-                slave_sql_running: row.get("Slave_SQL_Running"),
-                master_log_file: row.get("Master_Log_File"),
-                read_master_log_pos: row.get("Read_Master_Log_Pos"),
-                relay_log_file: row.get("Relay_Log_File"),
-                relay_log_pos: row.get("Relay_Log_Pos"),
-                relay_master_log_file: row.get("Relay_Master_Log_File"),
-                seconds_behind_master: read_behind_master,
-            };
-
-            result = data;
+        if let Some(row) = cursor.next().await? {
+            result.executed_gtid_set = get_text(&row, "gtid_slave_pos");
         }
+    }
 
-        Ok(result)
+    if let Some(heartbeat) = heartbeat {
+        let sql = heartbeat.lag_query()?;
+        let mut cursor = sqlx::query(&sql).fetch(&pool);
+
+        // A missing heartbeat table falls back to the server-reported lag
+        // rather than failing the whole poll.
+        match cursor.next().await {
+            Ok(Some(row)) => result.heartbeat_lag = get_optional_u64(&row, "heartbeat_lag"),
+            Ok(None) => {}
+            Err(error) => warn!(
+                "Heartbeat lag query on {} failed: {:?}",
+                heartbeat.table, error
+            ),
+        }
     }
+
+    Ok(result)
 }
 
 #[async_trait]
-impl Fetch<Result<String, Error>> for ConnectorPostgres {
-    async fn fetch_dbslave_status(&self) -> Result<String, Error> {
-        unimplemented!()
+impl Fetch<Result<DBSlaveStatus, Error>> for ConnectorMysql {
+    async fn fetch_dbslave_status(&self) -> Result<DBSlaveStatus, Error> {
+        let heartbeat = self.heartbeat.as_ref();
+
+        self.pool
+            .run(|pool| query_mysql_status(pool, heartbeat))
+            .await
     }
 }
 
-pub async fn fetch<T: 'static, U>(connector: T) -> U
+pub async fn fetch<T, U>(connector: &T) -> U
 where
     T: Fetch<U>,
 {
@@ -136,47 +357,20 @@ pub trait FetchMock<T> {
 #[async_trait]
 impl FetchMock<Result<DBSlaveStatus, Error>> for ConnectorMysql {
     async fn fetch_mock_status(&self) -> Result<DBSlaveStatus, Error> {
-        let mut status = DBSlaveStatus::default();
-        status.slave_io_running = String::from("Yes");
-        status.slave_sql_running = String::from("Yes");
-        status.seconds_behind_master = String::from("320");
+        let status = DBSlaveStatus {
+            slave_io_running: String::from("Yes"),
+            slave_sql_running: String::from("Yes"),
+            seconds_behind_master: Some(320),
+            ..DBSlaveStatus::default()
+        };
 
         Ok(status)
     }
 }
 
-#[async_trait]
-impl FetchMock<Result<String, Error>> for ConnectorPostgres {
-    async fn fetch_mock_status(&self) -> Result<String, Error> {
-        unimplemented!()
-    }
-}
-
-pub async fn fetch_mocked<T: 'static, U>(
-    connector: T,
-) -> U
+pub async fn fetch_mocked<T, U>(connector: &T) -> U
 where
     T: FetchMock<U>,
 {
     connector.fetch_mock_status().await
 }
-
-impl<DBSlaveStatus> SentAlerts<DBSlaveStatus> {
-    pub async fn initialise() -> Result<SentAlerts<DBSlaveStatus>, Error> {
-        let vec: VecDeque<Alert<DBSlaveStatus>> = VecDeque::new();
-
-        let sent_queue = SentAlerts { sent_queue: vec };
-
-        Ok(sent_queue)
-    }
-
-    pub async fn add(&mut self, alert: Alert<DBSlaveStatus>) -> Result<(), Error> {
-        self.sent_queue.push_back(alert);
-
-        Ok(())
-    }
-
-    pub async fn sent(&mut self) -> Result<&VecDeque<Alert<DBSlaveStatus>>, Error> {
-        Ok(&self.sent_queue)
-    }
-}
\ No newline at end of file
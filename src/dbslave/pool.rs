@@ -0,0 +1,165 @@
+use crate::errors::Error;
+use serde::Deserialize;
+use sqlx::{Connect, Pool};
+use std::fmt;
+use std::future::Future;
+use std::time::Duration;
+use tokio::sync::Mutex;
+
+fn default_max_connections() -> u32 {
+    2
+}
+
+fn default_connect_timeout() -> u64 {
+    10
+}
+
+fn default_query_timeout() -> u64 {
+    30
+}
+
+fn default_idle_timeout() -> Option<u64> {
+    Some(600)
+}
+
+fn default_max_lifetime() -> Option<u64> {
+    Some(1800)
+}
+
+fn default_health_check() -> bool {
+    true
+}
+
+/// Connection pool settings of a replica target, listed under `pool`.
+///
+/// Timeouts and lifetimes are in seconds.
+#[derive(Debug, Clone, Deserialize)]
+pub struct PoolConfig {
+    #[serde(default)]
+    pub min_connections: u32,
+    #[serde(default = "default_max_connections")]
+    pub max_connections: u32,
+    #[serde(default = "default_connect_timeout")]
+    pub connect_timeout: u64,
+    #[serde(default = "default_query_timeout")]
+    pub query_timeout: u64,
+    #[serde(default = "default_idle_timeout")]
+    pub idle_timeout: Option<u64>,
+    #[serde(default = "default_max_lifetime")]
+    pub max_lifetime: Option<u64>,
+    /// Ping connections before handing them out, replacing dead ones.
+    #[serde(default = "default_health_check")]
+    pub health_check: bool,
+}
+
+impl Default for PoolConfig {
+    fn default() -> Self {
+        PoolConfig {
+            min_connections: 0,
+            max_connections: default_max_connections(),
+            connect_timeout: default_connect_timeout(),
+            query_timeout: default_query_timeout(),
+            idle_timeout: default_idle_timeout(),
+            max_lifetime: default_max_lifetime(),
+            health_check: default_health_check(),
+        }
+    }
+}
+
+/// A connection pool kept across polling cycles.
+///
+/// The pool is only built on first use, so that an unreachable replica at
+/// startup is reported like any other failed poll, and it is rebuilt after
+/// a failed poll so that a replica which moved or restarted is reconnected.
+pub struct LazyPool<C>
+where
+    C: Connect,
+{
+    url: String,
+    config: PoolConfig,
+    pool: Mutex<Option<Pool<C>>>,
+}
+
+impl<C> fmt::Debug for LazyPool<C>
+where
+    C: Connect,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LazyPool<C>")
+            .field("config", &self.config)
+            .finish()
+    }
+}
+
+impl<C> LazyPool<C>
+where
+    C: Connect,
+{
+    pub fn new(url: &str, config: &PoolConfig) -> LazyPool<C> {
+        LazyPool {
+            url: String::from(url),
+            config: config.clone(),
+            pool: Mutex::new(None),
+        }
+    }
+
+    /// Get the pool, connecting first if there is none yet.
+    pub async fn get(&self) -> Result<Pool<C>, Error> {
+        let mut pool = self.pool.lock().await;
+
+        if let Some(pool) = pool.as_ref() {
+            return Ok(pool.clone());
+        }
+
+        let built = Pool::<C>::builder()
+            .min_size(self.config.min_connections)
+            .max_size(self.config.max_connections.max(1))
+            .connect_timeout(Duration::from_secs(self.config.connect_timeout))
+            .idle_timeout(self.config.idle_timeout.map(Duration::from_secs))
+            .max_lifetime(self.config.max_lifetime.map(Duration::from_secs))
+            .test_on_acquire(self.config.health_check)
+            .build(&self.url)
+            .await?;
+        info!(
+            "Connection pool built: {} connection(s) / {:?}",
+            built.size(),
+            self.config
+        );
+
+        *pool = Some(built.clone());
+        Ok(built)
+    }
+
+    /// Close and drop the pool, so that the next poll reconnects.
+    pub async fn reset(&self) {
+        if let Some(pool) = self.pool.lock().await.take() {
+            pool.close().await;
+        }
+    }
+
+    /// Run `query` against the pool within the configured query timeout,
+    /// resetting the pool if it fails.
+    pub async fn run<'a, F, Fut, T>(&'a self, query: F) -> Result<T, Error>
+    where
+        F: FnOnce(Pool<C>) -> Fut,
+        Fut: Future<Output = Result<T, Error>> + 'a,
+    {
+        let timeout = Duration::from_secs(self.config.query_timeout);
+        let result = match self.get().await {
+            Ok(pool) => match tokio::time::timeout(timeout, query(pool)).await {
+                Ok(result) => result,
+                Err(_) => Err(Error::Internal(format!(
+                    "Query timed out after {}s",
+                    self.config.query_timeout
+                ))),
+            },
+            Err(error) => Err(error),
+        };
+
+        if result.is_err() {
+            self.reset().await;
+        }
+
+        result
+    }
+}
@@ -0,0 +1,218 @@
+use crate::dbslave::{ConnectorPostgres, DBSlaveStatus, Fetch, FetchMock};
+use crate::errors::Error;
+use crate::sqlx::Cursor;
+use crate::sqlx::Row;
+use async_trait::async_trait;
+
+/// Streaming-replication status of a PostgreSQL standby.
+///
+/// Built from `pg_stat_wal_receiver` and the `pg_last_*` functions on the
+/// standby, and from `pg_stat_replication` on the primary when a
+/// `primary_url` is configured for the target.
+#[derive(Debug, Clone, Default)]
+pub struct PgReplicationStatus {
+    pub sender_host: String,
+    /// `pg_stat_wal_receiver.status`, e.g. `streaming`. Empty when the WAL
+    /// receiver is not running.
+    pub receiver_state: String,
+    pub in_recovery: bool,
+    pub replay_paused: bool,
+    pub receive_lsn: String,
+    pub replay_lsn: String,
+    pub replay_lag_bytes: u64,
+    /// `None` when the standby has not replayed any transaction yet.
+    pub replay_lag_seconds: Option<u64>,
+    /// `pg_stat_replication.state` for this standby, as seen by the primary.
+    pub primary_state: Option<String>,
+    /// Bytes between the primary's current WAL position and this standby's
+    /// replay position, as seen by the primary.
+    pub primary_lag_bytes: Option<u64>,
+}
+
+impl From<PgReplicationStatus> for DBSlaveStatus {
+    /// Map a standby's status onto the columns of `SHOW SLAVE STATUS`, so that
+    /// PostgreSQL targets flow through the same alert rules as MySQL. The
+    /// LSNs go to fields of their own, leaving the binlog coordinates empty.
+    fn from(status: PgReplicationStatus) -> DBSlaveStatus {
+        let yes_no = |value: bool| String::from(if value { "Yes" } else { "No" });
+        let known_to_primary = status.primary_state.as_deref() != Some("");
+
+        DBSlaveStatus {
+            master_host: status.sender_host,
+            slave_io_running: yes_no(status.receiver_state == "streaming" && known_to_primary),
+            slave_sql_running: yes_no(status.in_recovery && !status.replay_paused),
+            receive_lsn: status.receive_lsn,
+            replay_lsn: status.replay_lsn,
+            replay_lag_bytes: Some(
+                status
+                    .primary_lag_bytes
+                    .unwrap_or(0)
+                    .max(status.replay_lag_bytes),
+            ),
+            seconds_behind_master: status.replay_lag_seconds,
+            ..DBSlaveStatus::default()
+        }
+    }
+}
+
+async fn fetch_standby_status(pool: sqlx::PgPool) -> Result<PgReplicationStatus, Error> {
+    // Replay lag in seconds is only meaningful while WAL is outstanding; on an
+    // idle primary `pg_last_xact_replay_timestamp()` keeps getting older even
+    // though the standby is fully caught up. `pg_is_wal_replay_paused()`
+    // raises an error once the standby has been promoted.
+    let sql = "SELECT \
+        pg_is_in_recovery() AS in_recovery, \
+        CASE WHEN pg_is_in_recovery() THEN pg_is_wal_replay_paused() ELSE false END \
+            AS replay_paused, \
+        COALESCE(pg_last_wal_receive_lsn()::text, '') AS receive_lsn, \
+        COALESCE(pg_last_wal_replay_lsn()::text, '') AS replay_lsn, \
+        COALESCE(pg_wal_lsn_diff(pg_last_wal_receive_lsn(), pg_last_wal_replay_lsn()), 0)::bigint \
+            AS replay_lag_bytes, \
+        CASE WHEN pg_last_wal_receive_lsn() = pg_last_wal_replay_lsn() THEN 0 \
+            ELSE EXTRACT(EPOCH FROM now() - pg_last_xact_replay_timestamp())::bigint \
+        END AS replay_lag_seconds";
+    let mut cursor = sqlx::query(sql).fetch(&pool);
+    let mut result = PgReplicationStatus::default();
+
+    if let Some(row) = cursor.next().await? {
+        result.in_recovery = row.get("in_recovery");
+        result.replay_paused = row.get("replay_paused");
+        result.receive_lsn = row.get("receive_lsn");
+        result.replay_lsn = row.get("replay_lsn");
+        result.replay_lag_bytes = row.get::<i64, &str>("replay_lag_bytes").max(0) as u64;
+        result.replay_lag_seconds = row
+            .get::<Option<i64>, &str>("replay_lag_seconds")
+            .map(|seconds| seconds.max(0) as u64);
+    }
+    drop(cursor);
+
+    let sql = "SELECT status, COALESCE(sender_host, '') AS sender_host FROM pg_stat_wal_receiver";
+    let mut cursor = sqlx::query(sql).fetch(&pool);
+
+    if let Some(row) = cursor.next().await? {
+        result.receiver_state = row.get("status");
+        result.sender_host = row.get("sender_host");
+    }
+
+    Ok(result)
+}
+
+async fn fetch_primary_status(
+    pool: sqlx::PgPool,
+    application_name: &str,
+    status: &mut PgReplicationStatus,
+) -> Result<(), Error> {
+    let sql = "SELECT state, \
+        COALESCE(pg_wal_lsn_diff(pg_current_wal_lsn(), replay_lsn), 0)::bigint AS lag_bytes \
+        FROM pg_stat_replication WHERE application_name = $1";
+    let mut cursor = sqlx::query(sql).bind(application_name).fetch(&pool);
+
+    if let Some(row) = cursor.next().await? {
+        status.primary_state = Some(row.get("state"));
+        status.primary_lag_bytes = Some(row.get::<i64, &str>("lag_bytes").max(0) as u64);
+    } else {
+        // The primary does not know about this standby at all.
+        status.primary_state = Some(String::new());
+    }
+
+    Ok(())
+}
+
+#[async_trait]
+impl Fetch<Result<PgReplicationStatus, Error>> for ConnectorPostgres {
+    async fn fetch_dbslave_status(&self) -> Result<PgReplicationStatus, Error> {
+        let mut status = self.standby.run(fetch_standby_status).await?;
+
+        if let Some(primary) = &self.primary {
+            let application_name = &self.application_name;
+            let status = &mut status;
+            primary
+                .run(|pool| fetch_primary_status(pool, application_name, status))
+                .await?;
+        }
+
+        Ok(status)
+    }
+}
+
+#[async_trait]
+impl Fetch<Result<DBSlaveStatus, Error>> for ConnectorPostgres {
+    async fn fetch_dbslave_status(&self) -> Result<DBSlaveStatus, Error> {
+        let status =
+            <Self as Fetch<Result<PgReplicationStatus, Error>>>::fetch_dbslave_status(self).await?;
+
+        Ok(DBSlaveStatus::from(status))
+    }
+}
+
+#[async_trait]
+impl FetchMock<Result<DBSlaveStatus, Error>> for ConnectorPostgres {
+    async fn fetch_mock_status(&self) -> Result<DBSlaveStatus, Error> {
+        let status = PgReplicationStatus {
+            receiver_state: String::from("streaming"),
+            in_recovery: true,
+            replay_lag_bytes: 16384,
+            replay_lag_seconds: Some(320),
+            ..PgReplicationStatus::default()
+        };
+
+        Ok(DBSlaveStatus::from(status))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stopped_receiver_maps_to_io_not_running() {
+        let status = PgReplicationStatus {
+            receiver_state: String::new(),
+            in_recovery: true,
+            ..PgReplicationStatus::default()
+        };
+        let mapped = DBSlaveStatus::from(status);
+
+        assert_eq!("No", mapped.slave_io_running);
+        assert_eq!("Yes", mapped.slave_sql_running);
+    }
+
+    #[test]
+    fn test_standby_unknown_to_primary_maps_to_io_not_running() {
+        let status = PgReplicationStatus {
+            receiver_state: String::from("streaming"),
+            in_recovery: true,
+            primary_state: Some(String::new()),
+            ..PgReplicationStatus::default()
+        };
+
+        assert_eq!("No", DBSlaveStatus::from(status).slave_io_running);
+    }
+
+    #[test]
+    fn test_lag_bytes_prefers_larger_primary_view() {
+        let status = PgReplicationStatus {
+            receiver_state: String::from("streaming"),
+            replay_lag_bytes: 10,
+            primary_lag_bytes: Some(4096),
+            ..PgReplicationStatus::default()
+        };
+
+        assert_eq!(Some(4096), DBSlaveStatus::from(status).replay_lag_bytes);
+    }
+
+    #[test]
+    fn test_promoted_standby_maps_to_sql_not_running() {
+        let status = PgReplicationStatus {
+            in_recovery: false,
+            receive_lsn: String::from("0/3000148"),
+            replay_lsn: String::from("0/3000148"),
+            ..PgReplicationStatus::default()
+        };
+        let mapped = DBSlaveStatus::from(status);
+
+        assert_eq!("No", mapped.slave_sql_running);
+        assert_eq!("0/3000148", mapped.replay_lsn);
+        assert_eq!("", mapped.master_log_file);
+    }
+}
@@ -0,0 +1,121 @@
+use crate::alerts::Severity;
+use crate::configure;
+use crate::errors::Error;
+use serde::Deserialize;
+use std::fmt;
+
+/// Broad cause of a `Last_IO_Errno` / `Last_SQL_Errno`.
+///
+/// Data errors need a DBA to repair or re-seed the replica, whereas network
+/// errors usually clear by themselves once the master is reachable again.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ErrorClass {
+    Network,
+    Data,
+    Other,
+    /// Recorded in the report but never alerted on by itself.
+    Ignore,
+}
+
+impl fmt::Display for ErrorClass {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            ErrorClass::Network => "network",
+            ErrorClass::Data => "data",
+            ErrorClass::Other => "other",
+            ErrorClass::Ignore => "ignore",
+        };
+
+        write!(f, "{}", name)
+    }
+}
+
+impl ErrorClass {
+    /// The alert raised on an error of this class, so that rules, routing
+    /// and silences can tell the classes apart.
+    pub fn alert_name(&self) -> String {
+        format!("replication_error_{}", self)
+    }
+
+    /// Network errors usually clear by themselves; the others need a DBA.
+    pub fn severity(&self) -> Severity {
+        match self {
+            ErrorClass::Network => Severity::Warning,
+            _ => Severity::Critical,
+        }
+    }
+}
+
+/// Override the class of a specific errno, as listed under
+/// `replication_errors` in the config.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ErrnoRule {
+    pub errno: u32,
+    pub class: ErrorClass,
+}
+
+/// Load the errno overrides from config; empty when none are configured.
+pub fn rules() -> Result<Vec<ErrnoRule>, Error> {
+    Ok(configure::fetch_section::<Vec<ErrnoRule>>("replication_errors")?.unwrap_or_default())
+}
+
+fn default_class(errno: u32) -> ErrorClass {
+    match errno {
+        // ER_DUP_ENTRY, ER_KEY_NOT_FOUND, ER_NO_SUCH_TABLE, ER_BAD_TABLE_ERROR,
+        // ER_TABLE_EXISTS_ERROR, ER_NO_REFERENCED_ROW_2 and the row-based
+        // replication "can't find record" family.
+        1032 | 1050 | 1051 | 1062 | 1146 | 1452 | 1590 => ErrorClass::Data,
+        // ER_MASTER_FATAL_ERROR_READING_BINLOG, usually binlogs purged on the
+        // master: the replica has to be re-seeded or re-pointed.
+        1236 => ErrorClass::Data,
+        // CR_CONN_HOST_ERROR, CR_UNKNOWN_HOST, CR_SERVER_GONE_ERROR,
+        // CR_SERVER_LOST, the ER_NET_* read/write errors and
+        // ER_SLAVE_FATAL_ERROR.
+        1158..=1161 | 1593 | 2003 | 2005 | 2006 | 2013 => ErrorClass::Network,
+        _ => ErrorClass::Other,
+    }
+}
+
+/// Classify a replication errno, with configured overrides taking precedence.
+///
+/// Returns `None` for errno `0`, i.e. no error.
+pub fn classify(errno: u32, rules: &[ErrnoRule]) -> Option<ErrorClass> {
+    if errno == 0 {
+        return None;
+    }
+
+    let class = match rules.iter().find(|rule| rule.errno == errno) {
+        Some(rule) => rule.class,
+        None => default_class(errno),
+    };
+
+    Some(class)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_classify_defaults() {
+        assert_eq!(None, classify(0, &[]));
+        assert_eq!(Some(ErrorClass::Data), classify(1062, &[]));
+        assert_eq!(Some(ErrorClass::Data), classify(1146, &[]));
+        assert_eq!(Some(ErrorClass::Network), classify(2003, &[]));
+        assert_eq!(Some(ErrorClass::Network), classify(2013, &[]));
+        assert_eq!(Some(ErrorClass::Data), classify(1236, &[]));
+        assert_eq!(Some(ErrorClass::Other), classify(1045, &[]));
+    }
+
+    #[test]
+    fn test_classify_override() {
+        let rules = vec![ErrnoRule {
+            errno: 1062,
+            class: ErrorClass::Ignore,
+        }];
+
+        assert_eq!(Some(ErrorClass::Ignore), classify(1062, &rules));
+        assert_eq!(Some(ErrorClass::Data), classify(1146, &rules));
+    }
+}
@@ -0,0 +1,701 @@
+use crate::alerts::Severity;
+use crate::configure;
+use crate::dbslave::{DBSlaveStatus, ReplicaTarget};
+use crate::errors::Error;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fmt;
+
+/// A value read from a `DBSlaveStatus` field, or a rule threshold.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(untagged)]
+pub enum Value {
+    Bool(bool),
+    Number(f64),
+    Text(String),
+}
+
+impl Value {
+    fn as_number(&self) -> Option<f64> {
+        match self {
+            Value::Number(number) => Some(*number),
+            Value::Text(text) => text.trim().parse::<f64>().ok(),
+            Value::Bool(_) => None,
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Value::Bool(value) => write!(f, "{}", value),
+            Value::Number(value) => write!(f, "{}", value),
+            Value::Text(value) => write!(f, "{}", value),
+        }
+    }
+}
+
+impl DBSlaveStatus {
+    /// Look up a field by name for the rule engine.
+    ///
+    /// Returns `Ok(None)` for a NULL value and an error for an unknown field.
+    /// `lag_seconds` is the derived lag, see `DBSlaveStatus::lag_seconds`.
+    pub fn field(&self, name: &str) -> Result<Option<Value>, Error> {
+        let text = |value: &String| Some(Value::Text(value.clone()));
+        let number = |value: u64| Some(Value::Number(value as f64));
+        let optional = |value: Option<u64>| value.map(|value| Value::Number(value as f64));
+
+        let value = match name {
+            "master_host" => text(&self.master_host),
+            "master_user" => text(&self.master_user),
+            "slave_io_running" => text(&self.slave_io_running),
+            "slave_sql_running" => text(&self.slave_sql_running),
+            "master_log_file" => text(&self.master_log_file),
+            "read_master_log_pos" => number(self.read_master_log_pos),
+            "relay_log_file" => text(&self.relay_log_file),
+            "relay_log_pos" => number(self.relay_log_pos),
+            "relay_master_log_file" => text(&self.relay_master_log_file),
+            "seconds_behind_master" => optional(self.seconds_behind_master),
+            "heartbeat_lag" => optional(self.heartbeat_lag),
+            "lag_seconds" => optional(self.lag_seconds()),
+            "replay_lag_bytes" => optional(self.replay_lag_bytes),
+            "receive_lsn" => text(&self.receive_lsn),
+            "replay_lsn" => text(&self.replay_lsn),
+            "server_version" => text(&self.server_version),
+            "last_io_errno" => number(u64::from(self.last_io_errno)),
+            "last_io_error" => text(&self.last_io_error),
+            "last_io_error_timestamp" => text(&self.last_io_error_timestamp),
+            "last_sql_errno" => number(u64::from(self.last_sql_errno)),
+            "last_sql_error" => text(&self.last_sql_error),
+            "last_sql_error_timestamp" => text(&self.last_sql_error_timestamp),
+            "retrieved_gtid_set" => text(&self.retrieved_gtid_set),
+            "executed_gtid_set" => text(&self.executed_gtid_set),
+            "auto_position" => Some(Value::Bool(self.auto_position)),
+            _ => {
+                return Err(Error::Internal(format!(
+                    "Unknown field in alert rule: {}",
+                    name
+                )))
+            }
+        };
+
+        Ok(value)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+pub enum Operator {
+    #[serde(rename = "==", alias = "eq")]
+    Eq,
+    #[serde(rename = "!=", alias = "ne")]
+    Ne,
+    #[serde(rename = ">", alias = "gt")]
+    Gt,
+    #[serde(rename = ">=", alias = "ge")]
+    Ge,
+    #[serde(rename = "<", alias = "lt")]
+    Lt,
+    #[serde(rename = "<=", alias = "le")]
+    Le,
+    #[serde(rename = "is_null")]
+    IsNull,
+    #[serde(rename = "is_not_null")]
+    IsNotNull,
+}
+
+impl fmt::Display for Operator {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let symbol = match self {
+            Operator::Eq => "==",
+            Operator::Ne => "!=",
+            Operator::Gt => ">",
+            Operator::Ge => ">=",
+            Operator::Lt => "<",
+            Operator::Le => "<=",
+            Operator::IsNull => "is null",
+            Operator::IsNotNull => "is not null",
+        };
+
+        write!(f, "{}", symbol)
+    }
+}
+
+impl Operator {
+    fn is_ordering(self) -> bool {
+        matches!(
+            self,
+            Operator::Gt | Operator::Ge | Operator::Lt | Operator::Le
+        )
+    }
+
+    /// The operator that holds exactly when this one does not, for ordering
+    /// operators.
+    fn negated(self) -> Operator {
+        match self {
+            Operator::Gt => Operator::Le,
+            Operator::Ge => Operator::Lt,
+            Operator::Lt => Operator::Ge,
+            Operator::Le => Operator::Gt,
+            Operator::Eq => Operator::Ne,
+            Operator::Ne => Operator::Eq,
+            Operator::IsNull => Operator::IsNotNull,
+            Operator::IsNotNull => Operator::IsNull,
+        }
+    }
+
+    /// Compare a field value against a threshold.
+    ///
+    /// A NULL value only ever matches `is_null`, so that e.g. an unknown lag
+    /// never reads as "below threshold". Values are compared as numbers when
+    /// both sides are numeric, and as text otherwise.
+    pub fn compare(self, value: Option<&Value>, threshold: Option<&Value>) -> bool {
+        let (value, threshold) = match (self, value, threshold) {
+            (Operator::IsNull, value, _) => return value.is_none(),
+            (Operator::IsNotNull, value, _) => return value.is_some(),
+            (_, Some(value), Some(threshold)) => (value, threshold),
+            _ => return false,
+        };
+
+        let ordering = match (value.as_number(), threshold.as_number()) {
+            (Some(value), Some(threshold)) => value.partial_cmp(&threshold),
+            _ => match (value, threshold) {
+                (Value::Bool(value), Value::Bool(threshold)) => Some(value.cmp(threshold)),
+                _ => Some(value.to_string().cmp(&threshold.to_string())),
+            },
+        };
+
+        match ordering {
+            Some(ordering) => match self {
+                Operator::Eq => ordering.is_eq(),
+                Operator::Ne => ordering.is_ne(),
+                Operator::Gt => ordering.is_gt(),
+                Operator::Ge => ordering.is_ge(),
+                Operator::Lt => ordering.is_lt(),
+                Operator::Le => ordering.is_le(),
+                Operator::IsNull | Operator::IsNotNull => false,
+            },
+            None => false,
+        }
+    }
+}
+
+fn default_for_polls() -> u32 {
+    1
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+/// An alert condition on a `DBSlaveStatus` field, as listed under
+/// `alert_rules` in the config or under `rules` of a replica.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Rule {
+    /// Names the alert the rule raises.
+    pub name: String,
+    pub field: String,
+    pub operator: Operator,
+    pub threshold: Option<Value>,
+    #[serde(default)]
+    pub severity: Severity,
+    /// Only fire once the condition has held for this many consecutive polls.
+    #[serde(rename = "for", default = "default_for_polls")]
+    pub for_polls: u32,
+    /// Once firing, keep firing until the field no longer compares with
+    /// this threshold, e.g. fire above 300s and resolve at 60s or below.
+    /// Only for `>`, `>=`, `<` and `<=`; without it the rule resolves as
+    /// soon as its condition stops holding.
+    pub clear_threshold: Option<Value>,
+    /// Only resolve once the clear condition has held for this many
+    /// consecutive polls.
+    #[serde(default = "default_for_polls")]
+    pub clear_for: u32,
+    /// Set to `false` under a replica to switch off a global rule for it.
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+}
+
+impl Rule {
+    fn new(
+        name: &str,
+        field: &str,
+        operator: Operator,
+        threshold: Value,
+        severity: Severity,
+    ) -> Rule {
+        Rule {
+            name: String::from(name),
+            field: String::from(field),
+            operator,
+            threshold: Some(threshold),
+            severity,
+            for_polls: default_for_polls(),
+            clear_threshold: None,
+            clear_for: default_for_polls(),
+            enabled: default_enabled(),
+        }
+    }
+
+    fn validate(&self) -> Result<(), Error> {
+        DBSlaveStatus::default().field(&self.field)?;
+
+        let needs_threshold = !matches!(self.operator, Operator::IsNull | Operator::IsNotNull);
+        if needs_threshold && self.threshold.is_none() {
+            return Err(Error::Internal(format!(
+                "Alert rule {} needs a threshold for `{}`",
+                self.name, self.operator
+            )));
+        }
+
+        if self.for_polls < 1 || self.clear_for < 1 {
+            return Err(Error::Internal(format!(
+                "Alert rule {} needs `for` and `clear_for` of at least 1 poll",
+                self.name
+            )));
+        }
+
+        if let Some(clear_threshold) = &self.clear_threshold {
+            let numeric = |value: Option<&Value>| value.and_then(Value::as_number).is_some();
+            if !self.operator.is_ordering()
+                || !numeric(self.threshold.as_ref())
+                || !numeric(Some(clear_threshold))
+            {
+                return Err(Error::Internal(format!(
+                    "Alert rule {} can only have a clear_threshold with a numeric threshold and `>`, `>=`, `<` or `<=`",
+                    self.name
+                )));
+            }
+
+            // The clear condition must hold wherever the firing one does not,
+            // e.g. fire above 300 and clear at 60 or below, never at 400.
+            if self
+                .operator
+                .compare(Some(clear_threshold), self.threshold.as_ref())
+            {
+                return Err(Error::Internal(format!(
+                    "Alert rule {} has a clear_threshold of {} beyond its threshold",
+                    self.name, clear_threshold
+                )));
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn matches(&self, status: &DBSlaveStatus) -> Result<bool, Error> {
+        let value = status.field(&self.field)?;
+
+        Ok(self
+            .operator
+            .compare(value.as_ref(), self.threshold.as_ref()))
+    }
+
+    /// Whether a firing alert of this rule can resolve on `status`.
+    ///
+    /// A NULL value says nothing about the condition, e.g. the lag of a
+    /// replica whose threads just stopped, so it never resolves an alert,
+    /// except that of an `is_not_null` rule.
+    pub fn clears(&self, status: &DBSlaveStatus) -> Result<bool, Error> {
+        let value = match status.field(&self.field)? {
+            Some(value) => value,
+            None => return Ok(self.operator == Operator::IsNotNull),
+        };
+        let threshold = self.clear_threshold.as_ref().or(self.threshold.as_ref());
+
+        Ok(!self.operator.compare(Some(&value), threshold))
+    }
+}
+
+impl fmt::Display for Rule {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.threshold {
+            Some(threshold) => write!(f, "{} {} {}", self.field, self.operator, threshold)?,
+            None => write!(f, "{} {}", self.field, self.operator)?,
+        }
+        if let Some(clear_threshold) = &self.clear_threshold {
+            write!(
+                f,
+                " until {} {} {}",
+                self.field,
+                self.operator.negated(),
+                clear_threshold
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+/// The rules used when `alert_rules` is not configured, matching Sentinel's
+/// original checks.
+pub fn default_rules() -> Vec<Rule> {
+    vec![
+        Rule::new(
+            "io_thread_stopped",
+            "slave_io_running",
+            Operator::Eq,
+            Value::Text(String::from("No")),
+            Severity::Critical,
+        ),
+        Rule::new(
+            "sql_thread_stopped",
+            "slave_sql_running",
+            Operator::Eq,
+            Value::Text(String::from("No")),
+            Severity::Critical,
+        ),
+        Rule::new(
+            "replication_lag",
+            "lag_seconds",
+            Operator::Gt,
+            Value::Number(300.0),
+            Severity::Warning,
+        ),
+    ]
+}
+
+/// Load the global alert rules from config, or the default rules when none
+/// are configured.
+pub fn rules() -> Result<Vec<Rule>, Error> {
+    let rules = configure::fetch_section::<Vec<Rule>>("alert_rules")?.unwrap_or_else(default_rules);
+    for rule in &rules {
+        rule.validate()?;
+    }
+
+    Ok(rules)
+}
+
+/// The rules of a replica: the global rules, with those of the same name
+/// replaced by the replica's own, and disabled rules left out.
+pub fn rules_for(target: &ReplicaTarget, global: &[Rule]) -> Result<Vec<Rule>, Error> {
+    let mut rules: Vec<Rule> = global
+        .iter()
+        .filter(|rule| !target.rules.iter().any(|own| own.name == rule.name))
+        .cloned()
+        .collect();
+    rules.extend(target.rules.iter().cloned());
+    rules.retain(|rule| rule.enabled);
+
+    for rule in &rules {
+        rule.validate()?;
+    }
+
+    Ok(rules)
+}
+
+/// A rule whose condition has held for its required number of polls, and
+/// has not cleared since.
+#[derive(Debug, Clone)]
+pub struct RuleMatch {
+    pub rule: Rule,
+    pub value: Option<Value>,
+    /// Consecutive polls the rule has matched or kept firing for.
+    pub polls: u32,
+}
+
+impl RuleMatch {
+    pub fn summary(&self) -> String {
+        let value = match &self.value {
+            Some(value) => value.to_string(),
+            None => String::from("NULL"),
+        };
+
+        format!(
+            "{}: {} (is {}, for {} poll(s))",
+            self.rule.name, self.rule, value, self.polls
+        )
+    }
+}
+
+/// Where one rule stands on a replica.
+#[derive(Debug, Default)]
+struct Streak {
+    firing: bool,
+    /// Consecutive polls the rule has matched, or fired for.
+    polls: u32,
+    /// Consecutive polls a firing rule has met its clear condition.
+    clearing: u32,
+}
+
+/// Counts, per rule, the consecutive polls a replica has matched it, and
+/// the polls it has cleared for once firing.
+#[derive(Debug, Default)]
+pub struct RuleTracker {
+    streaks: HashMap<String, Streak>,
+}
+
+impl RuleTracker {
+    /// Evaluate `rules` against the latest poll and return those that fire.
+    pub fn evaluate(
+        &mut self,
+        rules: &[Rule],
+        status: &DBSlaveStatus,
+    ) -> Result<Vec<RuleMatch>, Error> {
+        let mut matches = Vec::new();
+
+        for rule in rules {
+            let streak = self.streaks.entry(rule.name.clone()).or_default();
+            if streak.firing {
+                streak.clearing = if rule.clears(status)? {
+                    streak.clearing + 1
+                } else {
+                    0
+                };
+                if streak.clearing >= rule.clear_for {
+                    *streak = Streak::default();
+                    continue;
+                }
+            } else if rule.matches(status)? {
+                streak.firing = streak.polls + 1 >= rule.for_polls;
+            } else {
+                streak.polls = 0;
+                continue;
+            }
+
+            streak.polls += 1;
+            if streak.firing {
+                matches.push(RuleMatch {
+                    rule: rule.clone(),
+                    value: status.field(&rule.field)?,
+                    polls: streak.polls,
+                });
+            }
+        }
+
+        Ok(matches)
+    }
+
+    /// Treat `rule` as having held for its required polls, e.g. for an alert
+    /// that was firing before a restart, so that it keeps firing rather than
+    /// resolving while the streak builds up again.
+    pub fn resume(&mut self, rule: &Rule) {
+        self.streaks.insert(
+            rule.name.clone(),
+            Streak {
+                firing: true,
+                polls: rule.for_polls.saturating_sub(1),
+                clearing: 0,
+            },
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn running(lag: Option<u64>) -> DBSlaveStatus {
+        DBSlaveStatus {
+            slave_io_running: String::from("Yes"),
+            slave_sql_running: String::from("Yes"),
+            seconds_behind_master: lag,
+            ..DBSlaveStatus::default()
+        }
+    }
+
+    #[test]
+    fn test_compare() {
+        let number = |value: f64| Some(Value::Number(value));
+        let text = |value: &str| Some(Value::Text(String::from(value)));
+
+        assert!(Operator::Gt.compare(number(301.0).as_ref(), number(300.0).as_ref()));
+        assert!(!Operator::Gt.compare(number(300.0).as_ref(), number(300.0).as_ref()));
+        assert!(Operator::Ge.compare(number(300.0).as_ref(), text("300").as_ref()));
+        assert!(Operator::Eq.compare(text("No").as_ref(), text("No").as_ref()));
+        assert!(Operator::Ne.compare(text("Yes").as_ref(), text("No").as_ref()));
+        assert!(!Operator::Lt.compare(None, number(10.0).as_ref()));
+        assert!(Operator::IsNull.compare(None, None));
+    }
+
+    #[test]
+    fn test_default_rules() {
+        let mut tracker = RuleTracker::default();
+        let rules = default_rules();
+
+        assert!(tracker
+            .evaluate(&rules, &running(Some(300)))
+            .unwrap()
+            .is_empty());
+
+        let matches = tracker.evaluate(&rules, &running(Some(301))).unwrap();
+        assert_eq!(1, matches.len());
+        assert_eq!("replication_lag", matches[0].rule.name);
+        assert_eq!(
+            "replication_lag: lag_seconds > 300 (is 301, for 1 poll(s))",
+            matches[0].summary()
+        );
+
+        let stopped = DBSlaveStatus {
+            slave_io_running: String::from("No"),
+            ..running(None)
+        };
+        // The unknown lag of a stopped replica keeps the lag alert firing.
+        let matches = tracker.evaluate(&rules, &stopped).unwrap();
+        let names: Vec<&str> = matches
+            .iter()
+            .map(|rule_match| &rule_match.rule.name[..])
+            .collect();
+        assert_eq!(vec!["io_thread_stopped", "replication_lag"], names);
+        assert_eq!(Severity::Critical, matches[0].rule.severity);
+    }
+
+    #[test]
+    fn test_for_consecutive_polls() {
+        let mut tracker = RuleTracker::default();
+        let mut rule = Rule::new(
+            "lagging",
+            "seconds_behind_master",
+            Operator::Gt,
+            Value::Number(60.0),
+            Severity::Warning,
+        );
+        rule.for_polls = 2;
+        let rules = vec![rule];
+
+        assert!(tracker
+            .evaluate(&rules, &running(Some(90)))
+            .unwrap()
+            .is_empty());
+        assert!(tracker
+            .evaluate(&rules, &running(Some(10)))
+            .unwrap()
+            .is_empty());
+        assert!(tracker
+            .evaluate(&rules, &running(Some(90)))
+            .unwrap()
+            .is_empty());
+        assert_eq!(
+            2,
+            tracker.evaluate(&rules, &running(Some(90))).unwrap()[0].polls
+        );
+    }
+
+    #[test]
+    fn test_resume_keeps_firing() {
+        let mut tracker = RuleTracker::default();
+        let mut rule = Rule::new(
+            "lagging",
+            "seconds_behind_master",
+            Operator::Gt,
+            Value::Number(60.0),
+            Severity::Warning,
+        );
+        rule.for_polls = 3;
+        tracker.resume(&rule);
+        let rules = vec![rule];
+
+        assert_eq!(
+            1,
+            tracker.evaluate(&rules, &running(Some(90))).unwrap().len()
+        );
+    }
+
+    #[test]
+    fn test_hysteresis() {
+        let mut tracker = RuleTracker::default();
+        let rule = Rule {
+            clear_threshold: Some(Value::Number(60.0)),
+            clear_for: 2,
+            ..Rule::new(
+                "lagging",
+                "seconds_behind_master",
+                Operator::Gt,
+                Value::Number(300.0),
+                Severity::Warning,
+            )
+        };
+        assert!(rule.validate().is_ok());
+        assert_eq!(
+            "seconds_behind_master > 300 until seconds_behind_master <= 60",
+            rule.to_string()
+        );
+        let rules = vec![rule];
+
+        let firing: Vec<bool> = [299, 301, 299, 100, 60, 100, 60, 30, 299]
+            .iter()
+            .map(|lag| {
+                !tracker
+                    .evaluate(&rules, &running(Some(*lag)))
+                    .unwrap()
+                    .is_empty()
+            })
+            .collect();
+
+        assert_eq!(
+            vec![false, true, true, true, true, true, true, false, false],
+            firing
+        );
+    }
+
+    #[test]
+    fn test_clear_threshold_beyond_threshold_is_rejected() {
+        let rule = Rule {
+            clear_threshold: Some(Value::Number(400.0)),
+            ..Rule::new(
+                "lagging",
+                "seconds_behind_master",
+                Operator::Gt,
+                Value::Number(300.0),
+                Severity::Warning,
+            )
+        };
+
+        assert!(rule.validate().is_err());
+    }
+
+    #[test]
+    fn test_unknown_field_is_rejected() {
+        let rule = Rule::new(
+            "typo",
+            "seconds_behind_mastr",
+            Operator::Gt,
+            Value::Number(60.0),
+            Severity::Warning,
+        );
+
+        assert!(rule.validate().is_err());
+    }
+
+    #[test]
+    fn test_null_value_does_not_clear() {
+        let mut tracker = RuleTracker::default();
+        let rules = vec![Rule::new(
+            "lagging",
+            "seconds_behind_master",
+            Operator::Gt,
+            Value::Number(300.0),
+            Severity::Warning,
+        )];
+
+        let firing: Vec<bool> = [Some(400), None, Some(100)]
+            .iter()
+            .map(|lag| !tracker.evaluate(&rules, &running(*lag)).unwrap().is_empty())
+            .collect();
+
+        assert_eq!(vec![true, true, false], firing);
+    }
+
+    #[test]
+    fn test_zero_polls_are_rejected() {
+        let rule = Rule::new(
+            "lagging",
+            "seconds_behind_master",
+            Operator::Gt,
+            Value::Number(300.0),
+            Severity::Warning,
+        );
+        assert!(rule.validate().is_ok());
+
+        let no_clear = Rule {
+            clear_for: 0,
+            ..rule.clone()
+        };
+        assert!(no_clear.validate().is_err());
+
+        let no_for = Rule {
+            for_polls: 0,
+            ..rule
+        };
+        assert!(no_for.validate().is_err());
+    }
+}
@@ -6,6 +6,7 @@ use std::io;
 #[derive(Debug)]
 pub enum Error {
     Chrono(chrono::ParseError),
+    Config(config::ConfigError),
     Hyper(hyper::Error),
     HyperHTTP(hyper::http::Error),
     Serde(error::Error),
@@ -55,6 +56,12 @@ impl From<sqlx::Error> for Error {
     }
 }
 
+impl From<config::ConfigError> for Error {
+    fn from(err: config::ConfigError) -> Error {
+        Error::Config(err)
+    }
+}
+
 impl From<chrono::ParseError> for Error {
     fn from(err: chrono::ParseError) -> Error {
         Error::Chrono(err)
@@ -87,14 +94,15 @@ impl std::error::Error for Error {
 
 impl std::fmt::Display for Error {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        write!(f, "Unknown error!")
+        match *self {
+            Error::Chrono(ref err) => write!(f, "Chrono Error: {}", err),
+            Error::Config(ref err) => write!(f, "Config Error: {}", err),
+            Error::Hyper(ref err) => write!(f, "Hyper Error: {}", err),
+            Error::HyperHTTP(ref err) => write!(f, "Hyper HTTP Error: {}", err),
+            Error::Serde(ref err) => write!(f, "Serde Error: {}", err),
+            Error::Sqlx(ref st) => write!(f, "{}", st),
+            Error::Internal(ref st) => write!(f, "Internal Error: {}", st),
+            _ => write!(f, "{:?}", self),
+        }
     }
 }
-
-// impl fmt::Display for Error {
-//   fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-//       match *self {
-//         Error::Internal(ref st) => write!(f, "Internal Error: {:?}", st),
-//       }
-//   }
-// }
@@ -6,6 +6,7 @@ extern crate glob;
 extern crate hyper;
 extern crate once_cell;
 extern crate rustc_serialize;
+extern crate serde;
 extern crate serde_json;
 extern crate sqlx;
 extern crate tokio;
@@ -33,10 +34,7 @@ pub static CONFIG: Lazy<config::Config> = Lazy::new(|| {
     let mut glob_path = "conf/development/*";
     let mut settings = Config::default();
 
-    let run_mode = match std::env::var("RUST_ENV") {
-        Ok(value) => value,
-        Err(_) => String::new(),
-    };
+    let run_mode = std::env::var("RUST_ENV").unwrap_or_default();
 
     if run_mode.eq("production") {
         glob_path = "conf/production/*";
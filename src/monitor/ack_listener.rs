@@ -0,0 +1,290 @@
+//! An embedded HTTP listener that takes acknowledgements, either posted as
+//! JSON to `/ack` or sent by the "Acknowledge" button of a Slack alert to
+//! `/slack/actions`. Either can name one alert, or a list of alerts such as
+//! the members of a grouped notification.
+//!
+//! For the button, the interactivity Request URL of the Slack app must
+//! point at `/slack/actions` on this listener.
+//!
+//! Every request must carry the configured token, as a `token` query
+//! parameter or an `Authorization: Bearer` header.
+//!
+//! A request is answered with `202 Accepted`: the acknowledgement only takes
+//! effect once a poll matches it to an open incident, and is dropped if none
+//! does within an hour.
+
+use crate::alerts::ack::Acknowledgement;
+use crate::configure;
+use crate::errors::Error;
+use chrono::Utc;
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Method, Request, Response, Server, StatusCode};
+use serde::Deserialize;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+
+/// Acknowledgements received since the last poll.
+pub type Inbox = Arc<Mutex<Vec<Acknowledgement>>>;
+
+/// The listener settings, configured under `ack_listener`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ListenerConfig {
+    /// Address to listen on, e.g. `0.0.0.0:8088`.
+    pub address: String,
+    pub token: String,
+}
+
+impl ListenerConfig {
+    pub fn load() -> Result<Option<ListenerConfig>, Error> {
+        let config = configure::fetch_section::<ListenerConfig>("ack_listener")?;
+        if let Some(config) = &config {
+            if config.token.is_empty() {
+                return Err(Error::Internal(String::from("ack_listener needs a token")));
+            }
+        }
+
+        Ok(config)
+    }
+}
+
+/// What to acknowledge; `by` and `comment` are optional.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct AckRequest {
+    pub target: String,
+    #[serde(alias = "rule")]
+    pub name: String,
+    #[serde(default)]
+    pub by: String,
+    #[serde(default)]
+    pub comment: String,
+}
+
+impl AckRequest {
+    pub fn acknowledgement(self) -> Acknowledgement {
+        Acknowledgement {
+            target: self.target,
+            name: self.name,
+            by: if self.by.is_empty() {
+                String::from("unknown")
+            } else {
+                self.by
+            },
+            comment: self.comment,
+            at: Utc::now(),
+        }
+    }
+}
+
+/// One alert to acknowledge, or several at once.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(untagged)]
+enum AckRequests {
+    One(AckRequest),
+    Many(Vec<AckRequest>),
+}
+
+impl AckRequests {
+    fn into_vec(self) -> Vec<AckRequest> {
+        match self {
+            AckRequests::One(request) => vec![request],
+            AckRequests::Many(requests) => requests,
+        }
+    }
+}
+
+/// Slack drops buttons whose value is longer than this.
+pub const BUTTON_VALUE_LIMIT: usize = 2000;
+
+/// The value carried by the "Acknowledge" button of a Slack alert.
+pub fn button_value(target: &str, name: &str) -> String {
+    serde_json::json!({ "target": target, "name": name }).to_string()
+}
+
+/// The value carried by the "Acknowledge" button of a grouped notification,
+/// acknowledging each of its alerts, given as `(target, name)`.
+pub fn group_button_value(alerts: &[(&str, &str)]) -> String {
+    let alerts: Vec<serde_json::Value> = alerts
+        .iter()
+        .map(|(target, name)| serde_json::json!({ "target": target, "name": name }))
+        .collect();
+
+    serde_json::Value::from(alerts).to_string()
+}
+
+/// Parse a Slack `block_actions` payload, sent form-encoded as `payload`.
+pub fn parse_slack_action(body: &[u8]) -> Result<Vec<AckRequest>, Error> {
+    let payload = url::form_urlencoded::parse(body)
+        .find(|(key, _)| key == "payload")
+        .map(|(_, value)| value.into_owned())
+        .ok_or(Error::UnexpectedJson)?;
+    let payload: serde_json::Value = serde_json::from_str(&payload)?;
+
+    let value = payload["actions"][0]["value"]
+        .as_str()
+        .ok_or(Error::UnexpectedJson)?;
+    let mut requests = serde_json::from_str::<AckRequests>(value)?.into_vec();
+
+    let user = &payload["user"];
+    let by = user["username"]
+        .as_str()
+        .or_else(|| user["name"].as_str())
+        .unwrap_or_default();
+    for request in &mut requests {
+        request.by = String::from(by);
+    }
+
+    Ok(requests)
+}
+
+fn authorised(request: &Request<Body>, token: &str) -> bool {
+    let query_token = request.uri().query().and_then(|query| {
+        url::form_urlencoded::parse(query.as_bytes())
+            .find(|(key, _)| key == "token")
+            .map(|(_, value)| value.into_owned())
+    });
+    let header_token = request
+        .headers()
+        .get(hyper::header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .map(String::from);
+
+    query_token.or(header_token).as_deref() == Some(token)
+}
+
+fn respond(status: StatusCode, text: &str) -> Response<Body> {
+    let mut response = Response::new(Body::from(String::from(text)));
+    *response.status_mut() = status;
+
+    response
+}
+
+async fn handle(
+    request: Request<Body>,
+    inbox: Inbox,
+    token: Arc<String>,
+) -> Result<Response<Body>, hyper::Error> {
+    if !authorised(&request, &token) {
+        return Ok(respond(StatusCode::UNAUTHORIZED, "Unauthorised"));
+    }
+    if request.method() != Method::POST {
+        return Ok(respond(StatusCode::METHOD_NOT_ALLOWED, "Use POST"));
+    }
+
+    let path = String::from(request.uri().path());
+    let body = hyper::body::to_bytes(request.into_body()).await?;
+    let parsed = match path.as_str() {
+        "/ack" => serde_json::from_slice::<AckRequests>(&body)
+            .map(AckRequests::into_vec)
+            .map_err(Error::from),
+        "/slack/actions" => parse_slack_action(&body),
+        _ => return Ok(respond(StatusCode::NOT_FOUND, "Not found")),
+    };
+
+    match parsed {
+        Ok(ack_requests) => {
+            let mut texts = Vec::new();
+            let mut acks = Vec::new();
+            for ack_request in ack_requests {
+                let ack = ack_request.acknowledgement();
+                info!(
+                    "Acknowledgement received: {} / {} by {}",
+                    ack.target, ack.name, ack.by
+                );
+                texts.push(format!(
+                    "Received acknowledgement of {} on {}",
+                    ack.name, ack.target
+                ));
+                acks.push(ack);
+            }
+            match inbox.lock() {
+                Ok(mut inbox) => inbox.extend(acks),
+                Err(_) => {
+                    return Ok(respond(
+                        StatusCode::INTERNAL_SERVER_ERROR,
+                        "Acknowledgements unavailable",
+                    ))
+                }
+            }
+
+            Ok(respond(StatusCode::ACCEPTED, &texts.join("\n")))
+        }
+        Err(error) => {
+            warn!("Invalid acknowledgement request to {}: {:?}", path, error);
+            Ok(respond(StatusCode::BAD_REQUEST, "Invalid acknowledgement"))
+        }
+    }
+}
+
+/// Start listening in the background, returning the inbox acknowledgements
+/// are delivered to.
+pub fn spawn(config: &ListenerConfig) -> Result<Inbox, Error> {
+    let address: SocketAddr = config.address.parse().map_err(|error| {
+        Error::Internal(format!(
+            "Invalid ack_listener address {}: {}",
+            config.address, error
+        ))
+    })?;
+    let inbox: Inbox = Arc::new(Mutex::new(Vec::new()));
+    let token = Arc::new(config.token.clone());
+
+    let service_inbox = inbox.clone();
+    let make_service = make_service_fn(move |_| {
+        let inbox = service_inbox.clone();
+        let token = token.clone();
+        async move {
+            Ok::<_, hyper::Error>(service_fn(move |request| {
+                handle(request, inbox.clone(), token.clone())
+            }))
+        }
+    });
+    let server = Server::try_bind(&address)?.serve(make_service);
+    info!("Listening for acknowledgements on {}", address);
+
+    tokio::spawn(async move {
+        if let Err(error) = server.await {
+            error!("Acknowledgement listener stopped: {}", error);
+        }
+    });
+
+    Ok(inbox)
+}
+
+/// Take the acknowledgements received since the last call.
+pub fn drain(inbox: &Inbox) -> Vec<Acknowledgement> {
+    match inbox.lock() {
+        Ok(mut inbox) => inbox.drain(..).collect(),
+        Err(_) => Vec::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_slack_action() {
+        let payload = serde_json::json!({
+            "type": "block_actions",
+            "user": { "id": "U1", "username": "dba" },
+            "actions": [{
+                "action_id": "acknowledge",
+                "value": button_value("replica", "replication_lag"),
+            }],
+        });
+        let body: String = url::form_urlencoded::Serializer::new(String::new())
+            .append_pair("payload", &payload.to_string())
+            .finish();
+
+        assert_eq!(
+            vec![AckRequest {
+                target: String::from("replica"),
+                name: String::from("replication_lag"),
+                by: String::from("dba"),
+                comment: String::new(),
+            }],
+            parse_slack_action(body.as_bytes()).unwrap()
+        );
+        assert!(parse_slack_action(b"payload=%7B%7D").is_err());
+    }
+}
@@ -1,5 +1,7 @@
 use super::errors::Error;
 use crate::alerts;
+use crate::alerts::antispam::{self, Decision, Evaluation};
+use crate::alerts::flapping::Flapping;
 use crate::configure;
 use crate::dbslave;
 use crate::dbslave::alertable;
@@ -7,28 +9,166 @@ use crate::log::LevelFilter;
 use crate::log4rs::append::file::FileAppender;
 use crate::log4rs::config::{Appender, Config, Root};
 use crate::log4rs::encode::pattern::PatternEncoder;
+use crate::services::notifier::Registry;
 use crate::utils;
 use crate::wrappers;
-use ::chrono::Utc;
-use std::collections::VecDeque;
+use ::chrono::{DateTime, Utc};
+use futures::future;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::mpsc;
 use std::sync::Arc;
 use std::time::Duration;
 use std::{thread, time};
 
-mod notify;
+mod ack_listener;
 
 #[derive(Default, Debug)]
 pub struct Alert<T> {
+    pub target: String,
+    /// Name of the rule or check that raised the alert.
+    pub name: String,
+    pub alert_type: alerts::AlertType,
+    pub severity: alerts::Severity,
+    /// A recovery notification rather than an alert.
+    pub resolved: bool,
+    /// Where the notification is delivered.
+    pub routes: Vec<alerts::routing::Route>,
     data: T,
     template: String,
+    message: String,
+    /// The alerts a summary was built from.
+    members: Vec<Alert<T>>,
     pub created_at: String,
 }
 
-#[derive(Default, Debug)]
-pub struct SentAlerts<T> {
-    pub sent_queue: VecDeque<Alert<T>>,
+impl<T> Alert<T> {
+    /// The replica status the alert was raised on. A summary has none of its
+    /// own; each of its `alerts()` carries its replica's.
+    pub fn data(&self) -> &T {
+        &self.data
+    }
+
+    /// The Slack Block Kit message.
+    pub fn template(&self) -> &str {
+        &self.template
+    }
+
+    /// The plain text message, with Slack `*bold*` markup.
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    /// The alerts this notification stands for: those of a summary, else
+    /// the alert itself.
+    pub fn alerts(&self) -> Vec<&Alert<T>> {
+        if self.members.is_empty() {
+            vec![self]
+        } else {
+            self.members.iter().collect()
+        }
+    }
+
+    /// The distinct values of `value` over `alerts()`, joined with commas,
+    /// e.g. every replica a summary covers.
+    pub fn describe<F: Fn(&Alert<T>) -> String>(&self, value: F) -> String {
+        let mut distinct: Vec<String> = Vec::new();
+        for alert in self.alerts() {
+            let value = value(alert);
+            if !distinct.contains(&value) {
+                distinct.push(value);
+            }
+        }
+
+        distinct.join(", ")
+    }
+}
+
+/// Everything remembered about a replica between polls.
+#[derive(Debug)]
+struct TargetState {
+    connector: dbslave::Connector,
+    rules: Vec<dbslave::rules::Rule>,
+    rule_tracker: dbslave::rules::RuleTracker,
+    /// What was notified per alert name, so that each is throttled
+    /// separately.
+    notify_states: HashMap<String, alerts::antispam::NotifyState>,
+    /// Lifecycle of each alert name, to announce recoveries.
+    alert_states: BTreeMap<String, alerts::state::AlertState>,
+    gtid_progress: dbslave::gtid::GtidProgress,
+    flap_detector: alerts::flapping::FlapDetector,
+    /// Alerts held back by a silence or flapping, recorded when first held
+    /// back.
+    held_back: HashSet<String>,
+}
+
+impl TargetState {
+    async fn initialise(
+        target: &dbslave::ReplicaTarget,
+        rules: &[dbslave::rules::Rule],
+    ) -> Result<TargetState, Error> {
+        Ok(TargetState {
+            connector: dbslave::Connector::new(target),
+            rules: dbslave::rules::rules_for(target, rules)?,
+            rule_tracker: dbslave::rules::RuleTracker::default(),
+            notify_states: HashMap::new(),
+            alert_states: BTreeMap::new(),
+            gtid_progress: dbslave::gtid::GtidProgress::default(),
+            flap_detector: alerts::flapping::FlapDetector::default(),
+            held_back: HashSet::new(),
+        })
+    }
+
+    /// Pick up the alert states and flap history of `target` saved before a
+    /// restart.
+    fn restore(&mut self, target: &str, snapshot: &alerts::store::Snapshot) {
+        if let Some(detector) = snapshot.flapping.get(target) {
+            self.flap_detector = detector.clone();
+        }
+        for ((saved_target, name), (alert_state, notify_state)) in &snapshot.states {
+            if saved_target == target {
+                if alert_state.status == alerts::state::Status::Firing {
+                    if let Some(rule) = self.rules.iter().find(|rule| &rule.name == name) {
+                        self.rule_tracker.resume(rule);
+                    }
+                }
+                self.alert_states.insert(name.clone(), alert_state.clone());
+                self.notify_states
+                    .insert(name.clone(), notify_state.clone());
+            }
+        }
+    }
+
+    /// Whether `ack` was applied to one of the replica's incidents.
+    fn acknowledged_by(&self, ack: &alerts::ack::Acknowledgement) -> bool {
+        self.notify_states
+            .values()
+            .any(|notify_state| notify_state.acknowledged.as_ref() == Some(ack))
+    }
+
+    fn alert_snapshot(
+        &self,
+    ) -> BTreeMap<String, (alerts::state::AlertState, alerts::antispam::NotifyState)> {
+        self.alert_states
+            .iter()
+            .map(|(name, alert_state)| {
+                let notify_state = self.notify_states.get(name).cloned().unwrap_or_default();
+                (name.clone(), (alert_state.clone(), notify_state))
+            })
+            .collect()
+    }
+}
+
+/// Save a record to the state store. A failure is logged rather than ending
+/// the watch, which matters more than surviving a restart.
+fn persist(store: &mut alerts::store::Store, record: &alerts::store::Record) {
+    if let Err(error) = store.append(record) {
+        error!(
+            "Failed to save alert state to {}: {}",
+            store.path().display(),
+            error
+        );
+    }
 }
 
 // Handler
@@ -103,7 +243,13 @@ impl RtmClient {
     }
 }
 
-async fn dbslave_notification_template(message: &str) -> Result<String, Error> {
+/// A Slack Block Kit message. An alert with an acknowledgement button value
+/// gets an "Acknowledge" button carrying it.
+async fn dbslave_notification_template(
+    message: &str,
+    resolved: bool,
+    ack_button: Option<&str>,
+) -> Result<String, Error> {
     let mut template = String::new();
     template.push_str(&String::from(
         r#"
@@ -113,13 +259,45 @@ async fn dbslave_notification_template(message: &str) -> Result<String, Error> {
           "type": "section",
           "text": {
             "type": "mrkdwn",
-            "text":  "Hello, this is an alert from your friendly *Sentinel*❗️"#,
+            "text":  ""#,
     ));
-    template.push_str(&message);
+    if resolved {
+        template.push_str("Hello, this is a recovery notice from your friendly *Sentinel*✅");
+    } else {
+        template.push_str("Hello, this is an alert from your friendly *Sentinel*❗️");
+    }
+    template.push_str(&utils::json_request::escape(message));
     template.push_str(&String::from(
         r#""
           }
-        }
+        }"#,
+    ));
+    if let Some(value) = ack_button {
+        template.push_str(&String::from(
+            r#",
+        {
+          "type": "actions",
+          "elements": [
+            {
+              "type": "button",
+              "action_id": "acknowledge",
+              "style": "primary",
+              "text": {
+                "type": "plain_text",
+                "text": "Acknowledge"
+              },
+              "value": ""#,
+        ));
+        template.push_str(&utils::json_request::escape(value));
+        template.push_str(&String::from(
+            r#""
+            }
+          ]
+        }"#,
+        ));
+    }
+    template.push_str(&String::from(
+        r#"
       ]
     }"#,
     ));
@@ -129,7 +307,436 @@ async fn dbslave_notification_template(message: &str) -> Result<String, Error> {
     Ok(template)
 }
 
+async fn build_alert(
+    target: &str,
+    firing: &alertable::Firing,
+    routes: Vec<alerts::routing::Route>,
+    acknowledgeable: bool,
+    data: dbslave::DBSlaveStatus,
+    created_at: String,
+) -> Result<Alert<dbslave::DBSlaveStatus>, Error> {
+    let message = format!("\n\n*Severity*: {}{}", firing.severity, firing.message);
+    let ack_button = if acknowledgeable && !firing.resolved {
+        Some(ack_listener::button_value(target, &firing.name))
+    } else {
+        None
+    };
+
+    Ok(Alert {
+        target: String::from(target),
+        name: firing.name.clone(),
+        alert_type: firing.alert_type,
+        severity: firing.severity,
+        resolved: firing.resolved,
+        routes,
+        data,
+        template: dbslave_notification_template(&message, firing.resolved, ack_button.as_deref())
+            .await?,
+        message,
+        members: Vec::new(),
+        created_at,
+    })
+}
+
+/// One notification summing up the alerts of a group, e.g. every replica
+/// of a primary that went down. The alerts must share their routes. Its
+/// acknowledgement button acknowledges each of them.
+async fn build_summary(
+    group: &str,
+    alerts: Vec<Alert<dbslave::DBSlaveStatus>>,
+    acknowledgeable: bool,
+) -> Result<Alert<dbslave::DBSlaveStatus>, Error> {
+    let first = alerts
+        .first()
+        .ok_or_else(|| Error::Internal(format!("Empty alert group {}", group)))?;
+    let severity = alerts
+        .iter()
+        .map(|alert| alert.severity)
+        .max()
+        .unwrap_or_default();
+    let resolved = alerts.iter().all(|alert| alert.resolved);
+
+    let mut summary = Alert {
+        alert_type: first.alert_type,
+        severity,
+        resolved,
+        routes: first.routes.clone(),
+        created_at: first.created_at.clone(),
+        members: alerts,
+        ..Alert::default()
+    };
+    summary.target = summary.describe(|alert| alert.target.clone());
+    summary.name = summary.describe(|alert| alert.name.clone());
+
+    let mut message = format!(
+        "\n\n*Grouped*: {} {} on replicas of {}\n*Replicas*: {}",
+        summary.members.len(),
+        if resolved { "recoveries" } else { "alerts" },
+        group,
+        summary.target
+    );
+    for alert in &summary.members {
+        message.push_str(&format!(
+            "\n\n———\n*Replica*: {}{}",
+            alert.target, alert.message
+        ));
+    }
+
+    let firing: Vec<(&str, &str)> = summary
+        .members
+        .iter()
+        .filter(|alert| !alert.resolved)
+        .map(|alert| (alert.target.as_str(), alert.name.as_str()))
+        .collect();
+    let ack_button = Some(ack_listener::group_button_value(&firing))
+        .filter(|_| acknowledgeable && !firing.is_empty())
+        .filter(|value| {
+            let fits = value.len() <= ack_listener::BUTTON_VALUE_LIMIT;
+            if !fits {
+                warn!("Too many alerts in {} for an acknowledgement button", group);
+            }
+            fits
+        });
+
+    summary.template =
+        dbslave_notification_template(&message, resolved, ack_button.as_deref()).await?;
+    summary.message = message;
+
+    Ok(summary)
+}
+
+/// Split the alerts of a group by their routes, so that each alert only
+/// reaches the channels of its own escalation step or severity.
+fn split_by_routes(
+    alerts: Vec<Alert<dbslave::DBSlaveStatus>>,
+) -> Vec<Vec<Alert<dbslave::DBSlaveStatus>>> {
+    let mut parts: Vec<Vec<Alert<dbslave::DBSlaveStatus>>> = Vec::new();
+    for alert in alerts {
+        match parts.iter_mut().find(|part| part[0].routes == alert.routes) {
+            Some(part) => part.push(alert),
+            None => parts.push(vec![alert]),
+        }
+    }
+
+    parts
+}
+
+/// The settings every poll is evaluated with.
+struct Settings {
+    antispam_policy: alerts::antispam::Policy,
+    errno_rules: Vec<dbslave::replication_error::ErrnoRule>,
+    flap_policy: Option<alerts::flapping::FlapPolicy>,
+    escalation: Option<alerts::escalation::EscalationPolicy>,
+    routing: alerts::routing::RoutingTable,
+    channels: Vec<alerts::routing::Channel>,
+    /// Whether alerts get an "Acknowledge" button.
+    acknowledgeable: bool,
+}
+
+/// What holds for every replica on one poll.
+struct Poll<'a> {
+    at: DateTime<Utc>,
+    acks: &'a [alerts::ack::Acknowledgement],
+    silences: &'a [alerts::silence::Silence],
+}
+
+/// Evaluate the latest poll of `target`, saving the state it leads to, and
+/// return the alerts to send.
+async fn evaluate_poll(
+    settings: &Settings,
+    target: &dbslave::ReplicaTarget,
+    state: &mut TargetState,
+    result: Result<dbslave::DBSlaveStatus, Error>,
+    poll: &Poll<'_>,
+    store: &mut alerts::store::Store,
+) -> Result<Vec<Alert<dbslave::DBSlaveStatus>>, Error> {
+    let saved_states = state.alert_snapshot();
+
+    // Acknowledgements apply to any open incident of the replica, whether or
+    // not it is evaluated on this poll, e.g. while the replica is unreachable.
+    for (name, notify_state) in state.notify_states.iter_mut() {
+        if let Some(ack) = poll
+            .acks
+            .iter()
+            .find(|ack| ack.applies_to(&target.name, name, notify_state))
+        {
+            info!(
+                " =>>>> {} / {}: acknowledged by {}",
+                target.name, name, ack.by
+            );
+            notify_state.acknowledged = Some(ack.clone());
+        }
+    }
+
+    // A replica that cannot be queried is alerted on like any other
+    // failure, and the watch carries on with the remaining replicas.
+    let polled = result.is_ok();
+    let (firings, query_data) = match result {
+        Ok(mut query_data) => {
+            let gtid_stall = state.gtid_progress.update(&query_data);
+            let rule_matches = state.rule_tracker.evaluate(&state.rules, &query_data)?;
+            let firings = alertable::run(
+                &target.name,
+                &mut query_data,
+                &settings.errno_rules,
+                gtid_stall.as_ref(),
+                &rule_matches,
+            )
+            .await?;
+
+            (firings, query_data)
+        }
+        Err(error) => {
+            let firing = alertable::unreachable(&target.name, &error).await?;
+
+            (vec![firing], dbslave::DBSlaveStatus::default())
+        }
+    };
+
+    let polled_at = poll.at;
+    for firing in &firings {
+        state
+            .alert_states
+            .entry(firing.name.clone())
+            .or_default()
+            .fire(
+                firing.alert_type,
+                firing.severity,
+                query_data.lag_seconds(),
+                polled_at,
+            );
+    }
+
+    let flapping = match &settings.flap_policy {
+        Some(flap_policy) => state.flap_detector.record(
+            firings.iter().map(|firing| firing.name.clone()).collect(),
+            flap_policy,
+        ),
+        None => Flapping::No,
+    };
+    if let Flapping::Started(_) | Flapping::Stopped = flapping {
+        persist(
+            store,
+            &alerts::store::Record::Flapping {
+                target: target.name.clone(),
+                detector: state.flap_detector.clone(),
+            },
+        );
+    }
+
+    // Alerts can only be seen to clear on a poll that succeeded.
+    let mut evaluations: Vec<(alertable::Firing, Evaluation)> = firings
+        .into_iter()
+        .map(|firing| {
+            let evaluation = Evaluation::Firing(firing.severity);
+            (firing, evaluation)
+        })
+        .collect();
+    if polled {
+        for (name, alert_state) in state.alert_states.iter_mut() {
+            if evaluations.iter().any(|(firing, _)| &firing.name == name) {
+                continue;
+            }
+            if let Some(incident) = alert_state.clear(polled_at) {
+                let recovery = alertable::resolved(&target.name, name, &incident).await?;
+                evaluations.push((recovery, Evaluation::Clear));
+            }
+        }
+    }
+
+    // Flapping is announced once when it starts and once when it
+    // stops, and holds back every other alert of the replica in
+    // between.
+    match flapping {
+        Flapping::Started(rate) => {
+            let firing = alertable::flapping(&target.name, rate).await?;
+            let evaluation = Evaluation::Firing(firing.severity);
+            evaluations.push((firing, evaluation));
+        }
+        Flapping::Stopped => {
+            let firing = alertable::flapping_stopped(&target.name).await?;
+            evaluations.push((firing, Evaluation::Clear));
+        }
+        Flapping::Ongoing | Flapping::No => {}
+    }
+    let flapping = state.flap_detector.is_flapping();
+
+    info!(
+        " =>>>> {}: Alert states {:?}",
+        target.name,
+        state
+            .alert_states
+            .iter()
+            .map(|(name, alert_state)| format!("{}: {}", name, alert_state.status))
+            .collect::<Vec<_>>()
+    );
+
+    let mut alerts = Vec::new();
+    for (mut firing, evaluation) in evaluations {
+        let notify_state = state.notify_states.entry(firing.name.clone()).or_default();
+        let reached_step = notify_state.escalation_step;
+        let (mut next, decision) = antispam::decide(
+            notify_state,
+            evaluation,
+            polled_at,
+            &settings.antispam_policy,
+        );
+        let silence = poll.silences.iter().find(|silence| {
+            silence.matches(&target.name, &firing.name, firing.severity, polled_at)
+        });
+        let held_back_by_flapping = flapping && firing.alert_type != alerts::AlertType::Flapping;
+        info!(
+            " =>>>> {} / {}: {:?} => {:?}{}",
+            target.name,
+            firing.name,
+            evaluation,
+            decision,
+            match silence {
+                Some(silence) => format!(" (silenced: {})", silence.comment),
+                None if held_back_by_flapping => String::from(" (flapping)"),
+                None => String::new(),
+            }
+        );
+
+        // A silenced alert is recorded, once, but not notified, and its
+        // notification state is left as it was: an incident notified before
+        // the silence still has its recovery announced, and one that was not
+        // is notified as new once the silence ends. Alerts of a flapping
+        // replica are held back the same way.
+        let silenced = (silence.is_some() || held_back_by_flapping)
+            && decision != Decision::Notify(antispam::Reason::Resolved);
+        if silenced {
+            if matches!(decision, Decision::Notify(_))
+                && state.held_back.insert(firing.name.clone())
+            {
+                persist(
+                    store,
+                    &alerts::store::Record::Sent(alerts::store::SentRecord {
+                        target: target.name.clone(),
+                        name: firing.name.clone(),
+                        severity: firing.severity,
+                        resolved: firing.resolved,
+                        silenced,
+                        created_at: wrappers::chrono::WrappedDateTime::default().to_rfc3339(),
+                    }),
+                );
+            }
+            continue;
+        }
+        state.held_back.remove(&firing.name);
+
+        // Unacknowledged incidents move along the escalation chain;
+        // each step is notified once, when it is reached, and every
+        // step reached hears of what follows.
+        let escalation = settings
+            .escalation
+            .as_ref()
+            .filter(|escalation| escalation.applies(firing.severity));
+        let escalated_to = escalation.and_then(|escalation| escalation.advance(&next, polled_at));
+        if let Some(step) = escalated_to {
+            next.escalation_step = step;
+        }
+        *notify_state = next;
+
+        let routes = match (escalation, decision, escalated_to) {
+            (Some(escalation), Decision::Notify(_), _) => {
+                escalation.routes(0, reached_step.max(notify_state.escalation_step))
+            }
+            (Some(escalation), _, Some(step)) => escalation.routes(step, step),
+            (Some(_), _, None) => Vec::new(),
+            (None, _, _) => settings.routing.routes(firing.severity, &settings.channels),
+        };
+
+        if let Some(step) = escalated_to {
+            info!(
+                " =>>>> {} / {}: escalated to step {}",
+                target.name, firing.name, step
+            );
+        }
+        if let (Some(_), Decision::Suppress) = (escalated_to, decision) {
+            let since = notify_state.unacknowledged_since.unwrap_or(polled_at);
+            firing.message.push_str(&format!(
+                "\n*Unacknowledged for*: {}",
+                utils::time::format_duration(polled_at - since)
+            ));
+        }
+
+        if matches!(decision, Decision::Notify(_)) || escalated_to.is_some() {
+            let alert = build_alert(
+                &target.name,
+                &firing,
+                routes,
+                settings.acknowledgeable,
+                query_data.clone(),
+                wrappers::chrono::WrappedDateTime::default().to_rfc3339(),
+            )
+            .await?;
+            persist(
+                store,
+                &alerts::store::Record::Sent(alerts::store::SentRecord {
+                    target: alert.target.clone(),
+                    name: alert.name.clone(),
+                    severity: alert.severity,
+                    resolved: alert.resolved,
+                    silenced,
+                    created_at: alert.created_at.clone(),
+                }),
+            );
+            alerts.push(alert);
+        }
+    }
+
+    for (name, (alert_state, notify_state)) in state.alert_snapshot() {
+        if saved_states.get(&name) != Some(&(alert_state.clone(), notify_state.clone())) {
+            persist(
+                store,
+                &alerts::store::Record::State {
+                    target: target.name.clone(),
+                    name,
+                    alert: alert_state,
+                    notify: notify_state,
+                },
+            );
+        }
+    }
+
+    Ok(alerts)
+}
+
+async fn poll_target(
+    connector: &dbslave::Connector,
+    enable_mock_data: bool,
+) -> Result<dbslave::DBSlaveStatus, Error> {
+    match connector {
+        dbslave::Connector::Mysql(connector) => poll_connector(connector, enable_mock_data).await,
+        dbslave::Connector::Postgres(connector) => {
+            poll_connector(connector, enable_mock_data).await
+        }
+    }
+}
+
+async fn poll_connector<T>(
+    connector: &T,
+    enable_mock_data: bool,
+) -> Result<dbslave::DBSlaveStatus, Error>
+where
+    T: dbslave::Fetch<Result<dbslave::DBSlaveStatus, Error>>
+        + dbslave::FetchMock<Result<dbslave::DBSlaveStatus, Error>>,
+{
+    if enable_mock_data {
+        dbslave::fetch_mocked(connector).await
+    } else {
+        dbslave::fetch(connector).await
+    }
+}
+
 pub async fn begin_watch() -> Result<(), Error> {
+    begin_watch_with(Registry::default()).await
+}
+
+/// Watch with the notification channels of `registry`, to which those
+/// listed in the config are added.
+pub async fn begin_watch_with(mut registry: Registry) -> Result<(), Error> {
     // "Night gathers, and now my watch begins. It shall not end until my death. I shall take no wife, hold no lands, father no children. I shall wear no crowns and win no glory. I shall live and die at my post. I am the sword in the darkness. I am the watcher on the walls. I am the shield that guards the realms of men. I pledge my life and honor to the Night's Watch, for this night and all the nights to come."
     // ―The Night's Watch oath
 
@@ -148,14 +755,9 @@ pub async fn begin_watch() -> Result<(), Error> {
     // Logging END
 
     // Configuration Options
-    // Antispam throttling threshold in minutes.
-    let antispam_threshold_config =
-        configure::fetch::<String>(String::from("antispam_threshold")).unwrap();
-    let antispam_threshold: i64 = antispam_threshold_config.parse::<i64>().unwrap();
-    info!(
-        "Configuration: antispam_threshold: {:#?}",
-        antispam_threshold
-    );
+    // Antispam throttling policy.
+    let antispam_policy = alerts::antispam::Policy::load()?;
+    info!("Configuration: antispam: {:#?}", antispam_policy);
 
     // Enabling mock data PREVENTS making actual calls to a live dbslave server.
     let enable_mock_data: bool =
@@ -175,9 +777,75 @@ pub async fn begin_watch() -> Result<(), Error> {
     let main_thread_pause_config =
         configure::fetch::<String>(String::from("main_thread_pause")).unwrap();
     let main_thread_pause: u64 = main_thread_pause_config.parse::<u64>().unwrap();
+    info!("Configuration: main_thread_pause: {:#?}", main_thread_pause);
+
+    let targets = dbslave::targets()?;
     info!(
-        "Configuration: antispam_threshold: {:#?}",
-        main_thread_pause
+        "Configuration: replicas: {:#?}",
+        targets
+            .iter()
+            .map(|target| &target.name)
+            .collect::<Vec<_>>()
+    );
+
+    let errno_rules = dbslave::replication_error::rules()?;
+    info!("Configuration: replication_errors: {:#?}", errno_rules);
+
+    let alert_rules = dbslave::rules::rules()?;
+    info!("Configuration: alert_rules: {:#?}", alert_rules);
+
+    registry.load()?;
+    let channels = registry.channels();
+    info!("Configuration: channels: {:?}", channels);
+
+    let routing = alerts::routing::RoutingTable::load()?;
+    registry.check(routing.named_channels())?;
+    info!("Configuration: routing: {:#?}", routing);
+
+    let flap_policy = alerts::flapping::FlapPolicy::load()?;
+    info!("Configuration: flapping: {:#?}", flap_policy);
+
+    // Alerts of replicas sharing a primary or cluster, held back for the
+    // grouping window and sent as one.
+    let grouping = alerts::grouping::GroupingPolicy::load()?;
+    info!("Configuration: grouping: {:#?}", grouping);
+    let mut grouper = grouping.as_ref().map(alerts::grouping::Grouper::new);
+
+    let escalation = alerts::escalation::EscalationPolicy::load()?;
+    if let Some(escalation) = &escalation {
+        registry.check(escalation.named_channels())?;
+    }
+    info!("Configuration: escalation: {:#?}", escalation);
+
+    let acks_path = alerts::ack::runtime_path()?;
+    info!("Configuration: acks_file: {}", acks_path);
+
+    // Acknowledgements over HTTP, and through Slack alert buttons.
+    let listener = ack_listener::ListenerConfig::load()?;
+    info!(
+        "Configuration: ack_listener: {:?}",
+        listener.as_ref().map(|listener| &listener.address)
+    );
+    let ack_inbox = match &listener {
+        Some(listener) => Some(ack_listener::spawn(listener)?),
+        None => None,
+    };
+
+    let settings = Settings {
+        antispam_policy,
+        errno_rules,
+        flap_policy,
+        escalation,
+        routing,
+        channels,
+        acknowledgeable: ack_inbox.is_some(),
+    };
+
+    let configured_silences = alerts::silence::configured()?;
+    let silences_path = alerts::silence::runtime_path()?;
+    info!(
+        "Configuration: silences: {:#?} / silences_file: {}",
+        configured_silences, silences_path
     );
 
     // Initialise main queue
@@ -186,9 +854,28 @@ pub async fn begin_watch() -> Result<(), Error> {
         .unwrap();
     info!("Queue initialised: {:#?}", queue);
 
-    // Inititalise sent queue
-    let mut sent_queue = SentAlerts::initialise().await.unwrap();
-    info!("Sent Queue initialised {:#?}", sent_queue);
+    // Alert state saved before a restart, so that ongoing incidents are not
+    // notified afresh.
+    let (mut store, snapshot) = alerts::store::Store::load()?;
+    info!(
+        "State store {} loaded: {} alert state(s), {} sent alert(s)",
+        store.path().display(),
+        snapshot.states.len(),
+        snapshot.sent.len()
+    );
+
+    // Inititalise state per replica, so that anti-spam throttling of one
+    // replica does not hold back alerts for another.
+    let mut target_states: HashMap<String, TargetState> = HashMap::new();
+    for target in &targets {
+        let mut state = TargetState::initialise(target, &alert_rules).await?;
+        state.restore(&target.name, &snapshot);
+        target_states.insert(target.name.clone(), state);
+    }
+    info!("Target states initialised {:#?}", target_states);
+
+    // Acknowledgements received over HTTP that matched no open incident yet.
+    let mut pending_acks: Vec<alerts::ack::Acknowledgement> = Vec::new();
 
     let mut loop_counter: i64 = 0;
 
@@ -197,153 +884,100 @@ pub async fn begin_watch() -> Result<(), Error> {
         let now = time::Instant::now();
         info!("MAIN Loop Start 🐶🐶🐶🐶🐶🐶 {}", loop_counter);
 
-        let mut query_data: dbslave::DBSlaveStatus;
-        if enable_mock_data {
-            query_data = dbslave::fetch_mocked::<
-                dbslave::ConnectorMysql,
-                Result<dbslave::DBSlaveStatus, Error>,
-            >(dbslave::ConnectorMysql {})
-            .await
-            .unwrap();
-        } else {
-            query_data = match dbslave::fetch::<
-                dbslave::ConnectorMysql,
-                Result<dbslave::DBSlaveStatus, Error>,
-            >(dbslave::ConnectorMysql {})
-            .await
-            {
-                Ok(val) => val,
-                Err(error) => panic!("Err: {:?}, {}:{}", error, file!(), line!()),
-            };
+        // Ad-hoc silences are picked up on every poll.
+        let mut silences = configured_silences.clone();
+        match alerts::silence::runtime(&silences_path) {
+            Ok(runtime_silences) => silences.extend(runtime_silences),
+            Err(error) => warn!("Ignoring silences in {}: {}", silences_path, error),
         }
 
-        // let db_status = check_dbslave(&query_data).await.unwrap();
-        let (notify_now, db_status) = alertable::run(&mut query_data).await?;
-        let slave_data = query_data.clone();
-
-        info!(" =>>>> Notify Now {}", notify_now);
-        if notify_now {
-            if sent_queue.sent_queue.len() <= 0 {
-                info!("🐤🐤🐤🐤🐤🐤🐤🐤🐤 Sent queue is empty.");
-
-                // Sent queue is empty, good to notify now.
-                let mut alert = Alert {
-                    data: slave_data.clone(),
-                    template: dbslave_notification_template(&db_status).await.unwrap(),
-                    created_at: wrappers::chrono::WrappedDateTime::default().to_rfc3339(),
-                };
-                queue.add(alert).await?;
-                info!("BR1: Main Queue: 🚀🚀🚀 Added alert to queue.");
-
-                alert = Alert {
-                    data: slave_data,
-                    template: dbslave_notification_template(&db_status).await.unwrap(),
-                    created_at: wrappers::chrono::WrappedDateTime::default().to_rfc3339(),
-                };
-                sent_queue.add(alert).await?;
-                info!("BR1: Sent Queue: 📦📦📦 Added sent alert to queue.");
-                info!(
-                    "BR1: 📦📦📦 Sent queue length {:#?}",
-                    sent_queue.sent_queue.len()
-                );
-            } else {
-                // Need to check last sent item to prevent spamming notifications
-                info!(
-                    "BR2: 📦📦📦 Sent queue length {:#?}",
-                    sent_queue.sent_queue.len()
+        // As are acknowledgements.
+        let mut acks = match alerts::ack::runtime(&acks_path) {
+            Ok(acks) => acks,
+            Err(error) => {
+                warn!("Ignoring acknowledgements in {}: {}", acks_path, error);
+                Vec::new()
+            }
+        };
+        // Those received over HTTP wait until they match an open incident,
+        // which may only be evaluated on a later poll.
+        if let Some(inbox) = &ack_inbox {
+            pending_acks.extend(ack_listener::drain(inbox));
+        }
+        let polled_at = Utc::now();
+        pending_acks.retain(|ack| {
+            let expired = ack.is_expired(polled_at);
+            if expired {
+                warn!(
+                    "Dropping acknowledgement of {} on {} by {}: no open incident matched it",
+                    ack.name, ack.target, ack.by
                 );
-                info!("BR2: About to `pop_back()` in Send queue");
-
-                // NOTE: Notice a call is made to `VecDeque::pop_back()` and any calls to
-                // `push_back()` will have a circular effect, i.e. the accumulator will
-                // not grow, but certainly the most recently pushed will be stored.
-                match sent_queue.sent_queue.pop_back() {
-                    Some(queue_item) => {
-                        info!("🐷🐷🐷🐷🐷🐷🐷 Sent Queue Inside SOME\n{:#?}", queue_item);
-                        info!("🐷🐷🐷🐷🐷🐷🐷 Some: Loop count {}", loop_counter);
-
-                        let parsed = utils::time::from_rfc_rfc3339(&queue_item.created_at);
-                        let parsed_ref = parsed.unwrap();
-                        let current_time = utils::time::parse_utc_time_to_rfc_rfc3339(Utc::now());
-                        let alert_timestamp = parsed_ref;
-                        let delta = current_time.naive_utc() - parsed_ref.naive_utc();
-                        info!("Current time {:#?}", current_time.to_rfc2822());
-                        info!("Alert parsed timestamp {:#?}", parsed_ref.to_rfc2822());
-
-                        let process_alerts = utils::time::occurred_more_than_mins_ago(
-                            alert_timestamp,
-                            current_time,
-                            antispam_threshold,
-                        );
-                        info!("Delta: {} s", delta.num_seconds());
-                        info!(
-                            "Alert occured before threshold({} mins)? {}",
-                            antispam_threshold, process_alerts
+            }
+            !expired
+        });
+        acks.extend(pending_acks.iter().cloned());
+
+        // Poll all replicas concurrently.
+        let polls = targets.iter().map(|target| {
+            let connector = &target_states[&target.name].connector;
+            poll_target(connector, enable_mock_data)
+        });
+        let results = future::join_all(polls).await;
+
+        let poll = Poll {
+            at: polled_at,
+            acks: &acks,
+            silences: &silences,
+        };
+        for (target, result) in targets.iter().zip(results) {
+            let state = target_states
+                .get_mut(&target.name)
+                .ok_or_else(|| Error::Internal(format!("No state for {}", target.name)))?;
+            let alerts = evaluate_poll(&settings, target, state, result, &poll, &mut store).await?;
+
+            for alert in alerts {
+                match (&grouping, &mut grouper) {
+                    (Some(grouping), Some(grouper)) => {
+                        let mut group = grouping.key(
+                            &target.name,
+                            target.cluster.as_deref(),
+                            &alert.data().master_host,
                         );
-                        info!("🐷🐷🐷🐷🐷🐷🐷 Some: Process??? {}", process_alerts);
-
-                        if process_alerts {
-                            // if true {
-                            let mut alert = Alert {
-                                data: slave_data.clone(),
-                                template: dbslave_notification_template(&db_status).await.unwrap(),
-                                created_at: wrappers::chrono::WrappedDateTime::default()
-                                    .to_rfc3339(),
-                            };
-                            queue.add(alert).await.unwrap();
-                            info!("Some: Main Queue: 🚀🚀🚀 Added alert to queue.");
-                            alert = Alert {
-                                data: slave_data,
-                                template: dbslave_notification_template(&db_status).await.unwrap(),
-                                created_at: wrappers::chrono::WrappedDateTime::default()
-                                    .to_rfc3339(),
-                            };
-
-                            // NOTE: Further enforce that we are performing a `push_back()`
-                            // on to the VecDeque, on which we previously performed a
-                            // `pop_back()`.
-                            let prev_len = sent_queue.sent_queue.len();
-                            info!("Some: Sent Queue: 📦📦📦 Sent queue length {:#?}", prev_len);
-
-                            sent_queue.add(alert).await.unwrap();
-                            info!("Some: Sent Queue: 📦📦📦 Added alert to SENT queue.");
-
-                            let sent_len = sent_queue.sent_queue.len();
-                            info!("Some: Sent Queue: 📦📦📦 Sent queue length {:#?}", sent_len);
-                            assert_ne!(prev_len, sent_len);
-                        } else {
-                            // Need to prevent the sent queue reaching 0, other wise logic will
-                            // flip over to branch BR1 there-by circumventing the
-                            // `process_alerts` spam guard.
-
-                            // Intentionally coerce `created_at` timestamp to allow entering
-                            // this branch of logic.
-
-                            let parsed = utils::time::from_rfc_rfc3339(&queue_item.created_at);
-                            let parsed_ref = parsed.unwrap();
-
-                            let alert = Alert {
-                                data: slave_data,
-                                template: dbslave_notification_template(&db_status).await.unwrap(),
-                                created_at: parsed_ref.to_rfc3339(),
-                            };
-                            sent_queue.add(alert).await.unwrap();
-                            info!(
-                "📦📦📦 Add coerced alert to Sent Queue to keep logic in BR2, with timestamp {:#?}",
-                parsed_ref.to_rfc2822()
-              );
+                        if alert.resolved {
+                            group.push_str(" (resolved)");
                         }
+                        grouper.add(group, alert, poll.at);
                     }
-                    None => panic!("In none..."),
+                    _ => queue.add(alert).await?,
+                }
+            }
+        }
+
+        pending_acks.retain(|ack| {
+            !target_states
+                .values()
+                .any(|state| state.acknowledged_by(ack))
+        });
+
+        if let Some(grouper) = &mut grouper {
+            for (group, alerts) in grouper.take_due(Utc::now()) {
+                for mut alerts in split_by_routes(alerts) {
+                    let alert = if alerts.len() == 1 {
+                        alerts.remove(0)
+                    } else {
+                        info!("Sending {} alerts of {} as one", alerts.len(), group);
+                        build_summary(&group, alerts, ack_inbox.is_some()).await?
+                    };
+                    queue.add(alert).await?;
                 }
             }
         }
-        // 🚀🚀🚀🚀🚀🚀🚀🚀🚀🚀🚀🚀🚀🚀🚀🚀🚀🚀
 
         // Threads handling
         let mut handler = Handler;
         let r_client = RtmClient::get_client(&mut handler).unwrap();
 
+        let mut handles = Vec::new();
         let mut loop_done = false;
         while !loop_done {
             let queue_len = queue.len().unwrap();
@@ -357,7 +991,7 @@ pub async fn begin_watch() -> Result<(), Error> {
                 let current_alert = queue.queue.remove(0);
                 let sender = r_client.sender().clone();
                 // Thread
-                let _ = thread::spawn(move || {
+                let handle = thread::spawn(move || {
                     info!("spawn thread: ...");
                     match sender.send_message(current_alert) {
                         Ok(value) => value,
@@ -368,15 +1002,20 @@ pub async fn begin_watch() -> Result<(), Error> {
                         now.elapsed()
                     );
                 });
-
-            // Disabled to prevent blocking the Main thread.
-            // let handle = _;
-            // handle.join().unwrap();
-            } else if queue_len <= 0 {
+                handles.push(handle);
+            } else {
                 loop_done = true;
             }
         }
 
+        // Wait for every alert to reach the channel, otherwise `try_iter()`
+        // below can run first and the notification is lost.
+        for handle in handles {
+            handle
+                .join()
+                .map_err(|_| Error::Internal(String::from("Alert sender thread panicked")))?;
+        }
+
         info!(
             "🚀🚀🚀 Queue is now empty! et voilà! Elapsed {:#?}\n\n{:#?}",
             now.elapsed(),
@@ -400,8 +1039,8 @@ pub async fn begin_watch() -> Result<(), Error> {
                     &utc_timestamp,
                     &elapsed,
                     &loop_counter,
-                    &alert.template,
-                    &db_status,
+                    &alert,
+                    &registry,
                 )
                 .await
                 .unwrap();
@@ -414,77 +1053,377 @@ pub async fn begin_watch() -> Result<(), Error> {
             main_thread_pause,
             now.elapsed()
         );
-        thread::sleep(time::Duration::from_millis(main_thread_pause));
+        // Yield to the runtime rather than block it, so that the
+        // acknowledgement listener keeps serving while the watch sleeps.
+        tokio::time::delay_for(time::Duration::from_millis(main_thread_pause)).await;
         info!("🚀 Continuing main thread. Elapsed: {:#?}", now.elapsed());
 
         info!("MAIN Loop Bottom 😸😸😸😸😸😸😸😸😸😸😸😸 {}", loop_counter);
 
-        loop_counter = loop_counter + 1;
+        loop_counter += 1;
     }
 }
 
 pub async fn process_notifications(
     enable_mocks: &bool,
-    now: &String,
+    now: &str,
     elapsed: &Duration,
     loop_count: &i64,
-    slack_template: &str,
-    email_template: &str,
+    alert: &Alert<dbslave::DBSlaveStatus>,
+    registry: &Registry,
 ) -> Result<(), Error> {
+    let channels = alert
+        .routes
+        .iter()
+        .map(|route| match &route.to {
+            Some(to) => format!("{} ({})", route.channel, to),
+            None => route.channel.to_string(),
+        })
+        .collect::<Vec<_>>();
+
     if *enable_mocks {
         println!(
-            "==> Mocked: Notification sent: Now: {} / Elapsed {:#?} / Loop {}",
-            *now, *elapsed, *loop_count
+            "==> Mocked: Notification sent: Now: {} / Elapsed {:#?} / Loop {} / Severity {} / Channels {:?}",
+            now, *elapsed, *loop_count, alert.severity, channels
         );
 
         info!(
-            "==> Mocked: Notification sent: Now: {} / Elapsed {:#?} / Loop {}",
-            *now, *elapsed, *loop_count
+            "==> Mocked: Notification sent: Now: {} / Elapsed {:#?} / Loop {} / Severity {} / Channels {:?}",
+            now, *elapsed, *loop_count, alert.severity, channels
         );
     } else {
-        // Notify Slack
-        notify::notify_slack(slack_template).await;
-        info!("==> Live: Notification to Slack sent: Now: {} / Elapsed {:#?} / Loop {}\nSlack Template: {:#?}",
-        *now,
-        *elapsed,
-        *loop_count,
-        slack_template
-      );
-
-        // Notify via Postmark
-        let beijing_timestamp = utils::time::get_beijing_timestamp_as_rfc2822();
-        let subject = String::from("Sentinel Monitoring Alert: DB Slave @ ")
-            + &beijing_timestamp
-            + " (Beijing)";
-
-        let from_address: String =
-            configure::fetch::<String>(String::from("postmark_from")).unwrap();
-        let replyto_address: String =
-            configure::fetch::<String>(String::from("postmark_replyto")).unwrap();
-        let to_address: String = configure::fetch::<String>(String::from("postmark_to")).unwrap();
-        let (response, response_value) = notify::notify_postmark(
-            &subject,
-            email_template,
-            &from_address,
-            &replyto_address,
-            &to_address,
-        )
-        .await
-        .unwrap();
-        info!("==> Live: Notification to Postmark sent: Now: {} / Elapsed {:#?} / Loop {}\nPostmark Template: {:#?} /Postmark Response Status: {}, Postmark Response: {:#?}",
-        *now,
-        *elapsed,
-        *loop_count,
-        email_template,
-        response.status(),
-        response_value
-      );
+        // A channel that fails does not hold back the others.
+        for route in &alert.routes {
+            let notifier = match registry.get(&route.channel) {
+                Some(notifier) => notifier,
+                None => {
+                    error!("No channel named {}", route.channel);
+                    continue;
+                }
+            };
+
+            match notifier.notify(alert, route).await {
+                Ok(()) => info!(
+                    "==> Live: Notification to {} sent: Now: {} / Elapsed {:#?} / Loop {}",
+                    route.channel, now, *elapsed, *loop_count
+                ),
+                Err(error) => error!(
+                    "==> Live: Notification to {} failed: {:?} / Now: {} / Loop {}",
+                    route.channel, error, now, *loop_count
+                ),
+            }
+        }
 
         println!(
-            "==> Live: Notification(s) sent: Now: {} / Elapsed {:#?} / Loop {}",
-            *now, *elapsed, *loop_count
+            "==> Live: Notification(s) sent: Now: {} / Elapsed {:#?} / Loop {} / Channels {:?}",
+            now, *elapsed, *loop_count, channels
         );
     }
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn settings() -> Settings {
+        Settings {
+            antispam_policy: antispam::Policy {
+                suppression_window: chrono::Duration::minutes(5),
+                repeat_interval: None,
+            },
+            errno_rules: Vec::new(),
+            flap_policy: None,
+            escalation: None,
+            routing: alerts::routing::RoutingTable::default(),
+            channels: vec![alerts::routing::Channel::new("slack")],
+            acknowledgeable: false,
+        }
+    }
+
+    fn replica() -> dbslave::ReplicaTarget {
+        serde_json::from_value(serde_json::json!({
+            "name": "db-1",
+            "url": "mysql://localhost/db",
+            "rules": [{
+                "name": "replication_lag",
+                "field": "lag_seconds",
+                "operator": ">",
+                "threshold": 300,
+                "clear_threshold": 60,
+            }],
+        }))
+        .unwrap()
+    }
+
+    fn running(lag: u64) -> Result<dbslave::DBSlaveStatus, Error> {
+        Ok(dbslave::DBSlaveStatus {
+            slave_io_running: String::from("Yes"),
+            slave_sql_running: String::from("Yes"),
+            seconds_behind_master: Some(lag),
+            ..dbslave::DBSlaveStatus::default()
+        })
+    }
+
+    fn start() -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339("2020-06-01T00:00:00Z")
+            .unwrap()
+            .with_timezone(&Utc)
+    }
+
+    /// Feed `(minutes since start, poll result)` through `evaluate_poll`,
+    /// with `silences` and `acks`, and collect the alerts sent on each poll
+    /// as `(name, resolved)`, along with what was saved to the store.
+    async fn watch(
+        name: &str,
+        settings: &Settings,
+        polls: Vec<(i64, Result<dbslave::DBSlaveStatus, Error>)>,
+        silences: &[alerts::silence::Silence],
+        acks: &[alerts::ack::Acknowledgement],
+    ) -> (Vec<Vec<(String, bool)>>, alerts::store::Snapshot) {
+        let path = std::env::temp_dir().join(format!(
+            "sentinel-monitor-{}-{}.jsonl",
+            name,
+            std::process::id()
+        ));
+        let _ = std::fs::remove_file(&path);
+        let (mut store, _) = alerts::store::Store::open(&path).unwrap();
+
+        let target = replica();
+        let mut state = TargetState::initialise(&target, &[]).await.unwrap();
+
+        let mut sent = Vec::new();
+        for (minutes, result) in polls {
+            let poll = Poll {
+                at: start() + chrono::Duration::minutes(minutes),
+                acks,
+                silences,
+            };
+            let alerts = evaluate_poll(settings, &target, &mut state, result, &poll, &mut store)
+                .await
+                .unwrap();
+            sent.push(
+                alerts
+                    .into_iter()
+                    .map(|alert| (alert.name, alert.resolved))
+                    .collect(),
+            );
+        }
+        drop(store);
+        let (_, snapshot) = alerts::store::Store::open(&path).unwrap();
+        let _ = std::fs::remove_file(&path);
+
+        (sent, snapshot)
+    }
+
+    #[tokio::test]
+    async fn test_unreachable_poll_keeps_rules_firing() {
+        let (sent, _) = watch(
+            "unreachable",
+            &settings(),
+            vec![
+                (0, running(400)),
+                (1, Err(Error::Internal(String::from("connection refused")))),
+                (2, running(200)),
+                (3, running(30)),
+            ],
+            &[],
+            &[],
+        )
+        .await;
+
+        let lag = |resolved| (String::from("replication_lag"), resolved);
+        assert_eq!(vec![lag(false)], sent[0]);
+        assert_eq!(vec![(String::from("unreachable"), false)], sent[1]);
+        assert!(!sent[2].contains(&lag(true)));
+        assert_eq!(vec![lag(true)], sent[3]);
+    }
+
+    #[tokio::test]
+    async fn test_notification_template_is_valid_json() {
+        let value = ack_listener::button_value("replica", "replication_lag");
+        let template = dbslave_notification_template("\n\n*Alert*: \"lag\"", false, Some(&value))
+            .await
+            .unwrap();
+        let data: serde_json::Value = serde_json::from_str(&template).unwrap();
+
+        assert!(data["blocks"][0]["text"]["text"]
+            .as_str()
+            .unwrap()
+            .ends_with("\n\n*Alert*: \"lag\""));
+        assert_eq!(
+            value,
+            data["blocks"][1]["elements"][0]["value"].as_str().unwrap()
+        );
+
+        let template = dbslave_notification_template("", true, None).await.unwrap();
+        let data: serde_json::Value = serde_json::from_str(&template).unwrap();
+        assert_eq!(1, data["blocks"].as_array().unwrap().len());
+    }
+
+    #[tokio::test]
+    async fn test_grouped_alerts_are_acknowledged_each() {
+        let slack = vec![alerts::routing::Route::new(alerts::routing::Channel::new(
+            "slack",
+        ))];
+        let mut members = Vec::new();
+        for target in &["a", "pg", "b"] {
+            let firing = alertable::flapping(target, 0.5).await.unwrap();
+            let routes = if *target == "b" {
+                Vec::new()
+            } else {
+                slack.clone()
+            };
+            members.push(
+                build_alert(
+                    target,
+                    &firing,
+                    routes,
+                    true,
+                    dbslave::DBSlaveStatus::default(),
+                    String::new(),
+                )
+                .await
+                .unwrap(),
+            );
+        }
+
+        // Alerts on other routes are not summed up with them.
+        let mut parts = split_by_routes(members);
+        assert_eq!(2, parts.len());
+        let summary = build_summary("main", parts.remove(0), true).await.unwrap();
+        assert_eq!("a, pg", summary.target);
+
+        let data: serde_json::Value = serde_json::from_str(summary.template()).unwrap();
+        let value = data["blocks"][1]["elements"][0]["value"].as_str().unwrap();
+        let payload = serde_json::json!({
+            "user": { "username": "dba" },
+            "actions": [{ "action_id": "acknowledge", "value": value }],
+        });
+        let body: String = url::form_urlencoded::Serializer::new(String::new())
+            .append_pair("payload", &payload.to_string())
+            .finish();
+        let acks: Vec<alerts::ack::Acknowledgement> =
+            ack_listener::parse_slack_action(body.as_bytes())
+                .unwrap()
+                .into_iter()
+                .map(ack_listener::AckRequest::acknowledgement)
+                .collect();
+
+        let notify_state = antispam::NotifyState {
+            firing: true,
+            notified: true,
+            unacknowledged_since: Some(Utc::now() - chrono::Duration::minutes(1)),
+            ..antispam::NotifyState::default()
+        };
+        for member in summary.alerts() {
+            assert!(acks.iter().any(|ack| ack.applies_to(
+                &member.target,
+                &member.name,
+                &notify_state
+            )));
+        }
+        assert_eq!(2, acks.len());
+    }
+
+    #[tokio::test]
+    async fn test_silence_keeps_recovery_of_notified_incident() {
+        let silence: alerts::silence::Silence = serde_json::from_value(serde_json::json!({
+            "target": "db-1",
+            "starts_at": start() + chrono::Duration::minutes(1),
+            "ends_at": start() + chrono::Duration::minutes(20),
+        }))
+        .unwrap();
+        let (sent, snapshot) = watch(
+            "silence",
+            &settings(),
+            vec![
+                (0, running(400)),
+                (1, running(400)),
+                (2, running(30)),
+                (10, running(400)),
+                (15, running(400)),
+                (20, running(400)),
+            ],
+            &[silence],
+            &[],
+        )
+        .await;
+
+        let lag = |resolved| vec![(String::from("replication_lag"), resolved)];
+        assert_eq!(
+            vec![lag(false), vec![], lag(true), vec![], vec![], lag(false)],
+            sent
+        );
+
+        // The incident that started during the silence is recorded once.
+        let silenced: Vec<bool> = snapshot.sent.iter().map(|sent| sent.silenced).collect();
+        assert_eq!(vec![false, false, true, false], silenced);
+    }
+
+    #[tokio::test]
+    async fn test_flapping_keeps_recovery_of_notified_incident() {
+        let settings = Settings {
+            flap_policy: Some(alerts::flapping::FlapPolicy {
+                window: 4,
+                flap_threshold: 0.5,
+                stable_threshold: 0.2,
+            }),
+            ..settings()
+        };
+        let (sent, snapshot) = watch(
+            "flapping",
+            &settings,
+            vec![
+                (0, running(400)),
+                (1, Err(Error::Internal(String::from("connection refused")))),
+                (2, running(400)),
+                (3, running(30)),
+            ],
+            &[],
+            &[],
+        )
+        .await;
+
+        assert_eq!(
+            vec![
+                (String::from("unreachable"), true),
+                (String::from("flapping"), false),
+            ],
+            sent[2]
+        );
+        assert_eq!(vec![(String::from("replication_lag"), true)], sent[3]);
+
+        // A restart picks up that the replica is flapping.
+        let mut state = TargetState::initialise(&replica(), &[]).await.unwrap();
+        state.restore("db-1", &snapshot);
+        assert!(state.flap_detector.is_flapping());
+    }
+
+    #[tokio::test]
+    async fn test_ack_applies_while_unreachable() {
+        let acks = vec![alerts::ack::Acknowledgement {
+            target: String::from("db-1"),
+            name: String::from("replication_lag"),
+            by: String::from("dba"),
+            comment: String::new(),
+            at: start() + chrono::Duration::minutes(1),
+        }];
+        let (_, snapshot) = watch(
+            "ack",
+            &settings(),
+            vec![
+                (0, running(400)),
+                (1, Err(Error::Internal(String::from("connection refused")))),
+            ],
+            &[],
+            &acks,
+        )
+        .await;
+
+        let (_, notify_state) =
+            &snapshot.states[&(String::from("db-1"), String::from("replication_lag"))];
+        assert_eq!(Some(&acks[0]), notify_state.acknowledged.as_ref());
+    }
+}
@@ -1,2 +1,7 @@
+pub mod notifier;
+pub mod opsgenie;
+pub mod pagerduty;
 pub mod postmark;
 pub mod slack;
+pub mod teams;
+pub mod webhook;
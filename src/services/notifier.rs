@@ -0,0 +1,180 @@
+//! Notification channels.
+//!
+//! Each channel listed under `channels` in the config is a `Notifier` built
+//! by the factory registered for its `kind`. Without a `channels` list, the
+//! channels are `slack` and `postmark`, configured by the top-level Slack and
+//! Postmark settings.
+//!
+//! A program using Sentinel as a library can register its own kinds, or
+//! ready-made notifiers, before starting the watch with
+//! `monitor::begin_watch_with`.
+
+use crate::alerts::routing::{Channel, Route};
+use crate::configure;
+use crate::dbslave::DBSlaveStatus;
+use crate::errors::Error;
+use crate::monitor::Alert;
+use crate::services::{opsgenie, pagerduty, postmark, slack, teams, webhook};
+use async_trait::async_trait;
+use serde::Deserialize;
+use std::collections::HashMap;
+
+#[async_trait]
+pub trait Notifier: Send + Sync {
+    /// Deliver `alert`. `route.to`, when given, names recipients to notify in
+    /// place of the channel's own.
+    async fn notify(&self, alert: &Alert<DBSlaveStatus>, route: &Route) -> Result<(), Error>;
+}
+
+/// Builds a notifier from its entry under `channels`.
+pub type Factory = fn(&config::Value) -> Result<Box<dyn Notifier>, Error>;
+
+fn default_enabled() -> bool {
+    true
+}
+
+#[derive(Debug, Deserialize)]
+struct ChannelEntry {
+    name: String,
+    kind: String,
+    #[serde(default = "default_enabled")]
+    enabled: bool,
+}
+
+/// The notification channels, by name.
+pub struct Registry {
+    kinds: HashMap<String, Factory>,
+    channels: Vec<(Channel, Box<dyn Notifier>)>,
+}
+
+impl Default for Registry {
+    /// A registry of the built-in kinds, without channels.
+    fn default() -> Self {
+        let mut registry = Registry {
+            kinds: HashMap::new(),
+            channels: Vec::new(),
+        };
+        registry.register_kind("slack", slack::SlackNotifier::from_config);
+        registry.register_kind("postmark", postmark::PostmarkNotifier::from_config);
+        registry.register_kind("webhook", webhook::WebhookNotifier::from_config);
+        registry.register_kind("pagerduty", pagerduty::PagerDutyNotifier::from_config);
+        registry.register_kind("opsgenie", opsgenie::OpsgenieNotifier::from_config);
+        registry.register_kind("teams", teams::TeamsNotifier::from_config);
+
+        registry
+    }
+}
+
+impl Registry {
+    /// Make `kind` available to channels in the config.
+    pub fn register_kind(&mut self, kind: &str, factory: Factory) {
+        self.kinds.insert(String::from(kind), factory);
+    }
+
+    /// Add a channel that is not in the config.
+    pub fn register(&mut self, name: &str, notifier: Box<dyn Notifier>) {
+        self.channels.push((Channel::new(name), notifier));
+    }
+
+    /// Add the channels configured under `channels`, or the default `slack`
+    /// and `postmark` channels.
+    pub fn load(&mut self) -> Result<(), Error> {
+        let entries = match configure::fetch_section::<Vec<config::Value>>("channels")? {
+            Some(entries) => entries,
+            None => {
+                let settings = config::Value::from(HashMap::<String, config::Value>::new());
+                self.register("slack", slack::SlackNotifier::from_config(&settings)?);
+                self.register(
+                    "postmark",
+                    postmark::PostmarkNotifier::from_config(&settings)?,
+                );
+                return Ok(());
+            }
+        };
+
+        for settings in entries {
+            let entry = settings.clone().try_into::<ChannelEntry>()?;
+            if !entry.enabled {
+                continue;
+            }
+            if self.get(&Channel::new(&entry.name)).is_some() {
+                return Err(Error::Internal(format!(
+                    "Duplicate channel name: {}",
+                    entry.name
+                )));
+            }
+
+            let factory = self.kinds.get(&entry.kind).ok_or_else(|| {
+                Error::Internal(format!(
+                    "Unknown kind `{}` of channel {}",
+                    entry.kind, entry.name
+                ))
+            })?;
+            let notifier = factory(&settings)?;
+            self.register(&entry.name, notifier);
+        }
+
+        Ok(())
+    }
+
+    pub fn get(&self, channel: &Channel) -> Option<&dyn Notifier> {
+        self.channels
+            .iter()
+            .find(|(name, _)| name == channel)
+            .map(|(_, notifier)| notifier.as_ref())
+    }
+
+    /// The names of every channel, in the order they were added.
+    pub fn channels(&self) -> Vec<Channel> {
+        self.channels.iter().map(|(name, _)| name.clone()).collect()
+    }
+
+    /// Check that every channel in `names` exists.
+    pub fn check<'a>(&self, names: impl IntoIterator<Item = &'a Channel>) -> Result<(), Error> {
+        for name in names {
+            if self.get(name).is_none() {
+                return Err(Error::Internal(format!(
+                    "Alerts are routed to unknown channel {}",
+                    name
+                )));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Read a setting of a channel, falling back to the top-level config flag
+/// `fallback` when the channel does not set it.
+pub fn setting(settings: &config::Value, key: &str, fallback: &str) -> Result<String, Error> {
+    let table = settings.clone().into_table()?;
+
+    match table.get(key) {
+        Some(value) => Ok(value.clone().into_str()?),
+        None => configure::fetch::<String>(String::from(fallback)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Nothing;
+
+    #[async_trait]
+    impl Notifier for Nothing {
+        async fn notify(&self, _: &Alert<DBSlaveStatus>, _: &Route) -> Result<(), Error> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_registered_channels() {
+        let mut registry = Registry::default();
+        registry.register("pager", Box::new(Nothing));
+
+        assert_eq!(vec![Channel::new("pager")], registry.channels());
+        assert!(registry.check(&[Channel::new("pager")]).is_ok());
+        assert!(registry.check(&[Channel::new("slack")]).is_err());
+    }
+}
@@ -0,0 +1,258 @@
+use crate::alerts::routing::Route;
+use crate::alerts::Severity;
+use crate::dbslave::DBSlaveStatus;
+use crate::errors::Error;
+use crate::monitor::Alert;
+use crate::services::notifier::Notifier;
+use crate::utils::json_request;
+use async_trait::async_trait;
+use hyper::{Body, Method, Response};
+use serde::Deserialize;
+
+fn default_url() -> String {
+    String::from("https://api.opsgenie.com")
+}
+
+/// Opsgenie priority of each severity.
+#[derive(Debug, Clone, Deserialize)]
+struct Priorities {
+    #[serde(default = "Priorities::critical")]
+    critical: String,
+    #[serde(default = "Priorities::warning")]
+    warning: String,
+    #[serde(default = "Priorities::info")]
+    info: String,
+}
+
+impl Priorities {
+    fn critical() -> String {
+        String::from("P1")
+    }
+
+    fn warning() -> String {
+        String::from("P3")
+    }
+
+    fn info() -> String {
+        String::from("P5")
+    }
+
+    fn get(&self, severity: Severity) -> &str {
+        match severity {
+            Severity::Info => &self.info,
+            Severity::Warning => &self.warning,
+            Severity::Critical => &self.critical,
+        }
+    }
+
+    fn validate(&self) -> Result<(), Error> {
+        for priority in &[&self.critical, &self.warning, &self.info] {
+            if !["P1", "P2", "P3", "P4", "P5"].contains(&priority.as_str()) {
+                return Err(Error::Internal(format!(
+                    "Invalid Opsgenie priority {}, expected P1 to P5",
+                    priority
+                )));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Default for Priorities {
+    fn default() -> Self {
+        Priorities {
+            critical: Priorities::critical(),
+            warning: Priorities::warning(),
+            info: Priorities::info(),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct OpsgenieConfig {
+    api_key: String,
+    #[serde(default = "default_url")]
+    url: String,
+    #[serde(default)]
+    priorities: Priorities,
+}
+
+/// Creates Opsgenie alerts through the Alerts API, aliased by replica and
+/// rule so that a recovery closes the alert it raised.
+#[derive(Debug)]
+pub struct OpsgenieNotifier {
+    api_key: String,
+    url: String,
+    priorities: Priorities,
+}
+
+impl OpsgenieNotifier {
+    pub fn from_config(settings: &config::Value) -> Result<Box<dyn Notifier>, Error> {
+        let config = settings.clone().try_into::<OpsgenieConfig>()?;
+        if config.api_key.is_empty() {
+            return Err(Error::Internal(String::from(
+                "Opsgenie channel without an api_key",
+            )));
+        }
+        config.priorities.validate()?;
+
+        Ok(Box::new(OpsgenieNotifier {
+            api_key: config.api_key,
+            url: String::from(config.url.trim_end_matches('/')),
+            priorities: config.priorities,
+        }))
+    }
+
+    async fn post(
+        &self,
+        url: &str,
+        data: &serde_json::Value,
+    ) -> Result<(Response<Body>, hyper::body::Bytes), Box<dyn std::error::Error + Send + Sync>>
+    {
+        let headers = [(
+            String::from("Authorization"),
+            format!("GenieKey {}", self.api_key),
+        )];
+
+        json_request::send(Method::POST, url, &headers, Body::from(data.to_string())).await
+    }
+}
+
+#[async_trait]
+impl Notifier for OpsgenieNotifier {
+    /// Create or close one Opsgenie alert per alert of a summary.
+    async fn notify(&self, alert: &Alert<DBSlaveStatus>, _route: &Route) -> Result<(), Error> {
+        for alert in alert.alerts() {
+            let (url, data) = if alert.resolved {
+                (close_url(&self.url, &alias(alert)), close(alert))
+            } else {
+                (
+                    format!("{}/v2/alerts", self.url),
+                    create(alert, self.priorities.get(alert.severity))?,
+                )
+            };
+
+            let (response, body) = self
+                .post(&url, &data)
+                .await
+                .map_err(|error| Error::Internal(format!("Opsgenie: {}", error)))?;
+
+            let body_string = String::from_utf8_lossy(&body);
+            if !response.status().is_success() {
+                return Err(Error::Internal(format!(
+                    "Opsgenie answered {}: {}",
+                    response.status(),
+                    body_string
+                )));
+            }
+
+            info!(
+                "Opsgenie Response Status: {}, Opsgenie Response: {}",
+                response.status(),
+                body_string
+            );
+        }
+
+        Ok(())
+    }
+}
+
+/// The Opsgenie alert of an alert: one per replica and rule.
+fn alias(alert: &Alert<DBSlaveStatus>) -> String {
+    format!("sentinel/{}/{}", alert.target, alert.name)
+}
+
+fn close_url(base: &str, alias: &str) -> String {
+    let alias: String = url::form_urlencoded::byte_serialize(alias.as_bytes()).collect();
+
+    format!(
+        "{}/v2/alerts/{}/close?identifierType=alias",
+        base,
+        alias.replace('+', "%20")
+    )
+}
+
+fn close(alert: &Alert<DBSlaveStatus>) -> serde_json::Value {
+    serde_json::json!({
+        "source": "Sentinel",
+        "note": format!("{} recovered on replica {}", alert.name, alert.target),
+    })
+}
+
+fn create(alert: &Alert<DBSlaveStatus>, priority: &str) -> Result<serde_json::Value, Error> {
+    let status = alert.data();
+
+    // Opsgenie details are strings only.
+    let mut details = serde_json::Map::new();
+    if let serde_json::Value::Object(fields) = serde_json::to_value(status)? {
+        for (field, value) in fields {
+            let value = match value {
+                serde_json::Value::String(value) => value,
+                serde_json::Value::Null => continue,
+                value => value.to_string(),
+            };
+            details.insert(field, serde_json::Value::String(value));
+        }
+    }
+    if let Some(lag) = status.lag_seconds() {
+        details.insert(String::from("lag_seconds"), lag.to_string().into());
+    }
+
+    let description = alert.message();
+
+    // Opsgenie truncates messages at 130 characters and descriptions at
+    // 15000.
+    let message: String = format!(
+        "[{}] {} on replica {}",
+        alert.severity.to_string().to_uppercase(),
+        alert.name,
+        alert.target
+    )
+    .chars()
+    .take(130)
+    .collect();
+
+    Ok(serde_json::json!({
+        "message": message,
+        "alias": alias(alert),
+        "description": description.trim().chars().take(15000).collect::<String>(),
+        "details": details,
+        "entity": alert.target,
+        "source": "Sentinel",
+        "priority": priority,
+        "tags": [alert.alert_type.to_string(), alert.severity.to_string()],
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_create_and_close() {
+        let mut alert: Alert<DBSlaveStatus> = Alert::default();
+        alert.target = String::from("db 1");
+        alert.name = String::from("replication_lag");
+        alert.severity = Severity::Warning;
+
+        let priorities = Priorities::default();
+        assert!(priorities.validate().is_ok());
+        let data = create(&alert, priorities.get(alert.severity)).unwrap();
+        assert_eq!("P3", data["priority"]);
+        assert_eq!("sentinel/db 1/replication_lag", data["alias"]);
+        assert!(data["details"]["read_master_log_pos"].is_string());
+        assert!(data["details"].get("seconds_behind_master").is_none());
+
+        assert_eq!(
+            "https://api.opsgenie.com/v2/alerts/sentinel%2Fdb%201%2Freplication_lag/close?identifierType=alias",
+            close_url(&default_url(), &alias(&alert))
+        );
+
+        let priorities = Priorities {
+            critical: String::from("P0"),
+            ..Priorities::default()
+        };
+        assert!(priorities.validate().is_err());
+    }
+}
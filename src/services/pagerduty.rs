@@ -0,0 +1,225 @@
+use crate::alerts::routing::Route;
+use crate::alerts::Severity;
+use crate::dbslave::DBSlaveStatus;
+use crate::errors::Error;
+use crate::monitor::Alert;
+use crate::services::notifier::Notifier;
+use crate::utils::json_request;
+use async_trait::async_trait;
+use hyper::Body;
+use serde::Deserialize;
+
+fn default_url() -> String {
+    String::from("https://events.pagerduty.com/v2/enqueue")
+}
+
+#[derive(Debug, Deserialize)]
+struct PagerDutyConfig {
+    routing_key: String,
+    #[serde(default = "default_url")]
+    url: String,
+}
+
+/// Pages through the PagerDuty Events API v2. Each alert triggers an incident
+/// keyed on its replica and rule, which its recovery resolves.
+#[derive(Debug)]
+pub struct PagerDutyNotifier {
+    routing_key: String,
+    url: String,
+}
+
+impl PagerDutyNotifier {
+    pub fn from_config(settings: &config::Value) -> Result<Box<dyn Notifier>, Error> {
+        let config = settings.clone().try_into::<PagerDutyConfig>()?;
+        if config.routing_key.is_empty() {
+            return Err(Error::Internal(String::from(
+                "PagerDuty channel without a routing_key",
+            )));
+        }
+
+        Ok(Box::new(PagerDutyNotifier {
+            routing_key: config.routing_key,
+            url: config.url,
+        }))
+    }
+}
+
+#[async_trait]
+impl Notifier for PagerDutyNotifier {
+    /// Send one event per alert of a summary, so that each incident is
+    /// resolved on its own.
+    async fn notify(&self, alert: &Alert<DBSlaveStatus>, _route: &Route) -> Result<(), Error> {
+        for alert in alert.alerts() {
+            let data = event(&self.routing_key, alert)?;
+            let (response, body) = json_request::post(&self.url, Body::from(data.to_string()))
+                .await
+                .map_err(|error| Error::Internal(format!("PagerDuty: {}", error)))?;
+
+            let body_string = String::from_utf8_lossy(&body);
+            if !response.status().is_success() {
+                return Err(Error::Internal(format!(
+                    "PagerDuty answered {}: {}",
+                    response.status(),
+                    body_string
+                )));
+            }
+
+            info!(
+                "PagerDuty Response Status: {}, PagerDuty Response: {}",
+                response.status(),
+                body_string
+            );
+        }
+
+        Ok(())
+    }
+}
+
+/// The incident of an alert: one per replica and rule.
+fn dedup_key(alert: &Alert<DBSlaveStatus>) -> String {
+    format!("sentinel/{}/{}", alert.target, alert.name)
+}
+
+fn severity(severity: Severity) -> &'static str {
+    match severity {
+        Severity::Info => "info",
+        Severity::Warning => "warning",
+        Severity::Critical => "critical",
+    }
+}
+
+/// The `trigger` event of an alert, or the `resolve` event of a recovery.
+fn event(routing_key: &str, alert: &Alert<DBSlaveStatus>) -> Result<serde_json::Value, Error> {
+    if alert.resolved {
+        return Ok(serde_json::json!({
+            "routing_key": routing_key,
+            "event_action": "resolve",
+            "dedup_key": dedup_key(alert),
+        }));
+    }
+
+    let status = alert.data();
+    let mut details = serde_json::to_value(status)?;
+    details["lag_seconds"] = serde_json::json!(status.lag_seconds());
+    details["rule"] = serde_json::json!(alert.name);
+
+    // PagerDuty rejects summaries over 1024 characters.
+    let summary: String = format!(
+        "[{}] {} alert {} on replica {}",
+        alert.severity.to_string().to_uppercase(),
+        alert.alert_type,
+        alert.name,
+        alert.target
+    )
+    .chars()
+    .take(1024)
+    .collect();
+
+    let mut payload = serde_json::json!({
+        "summary": summary,
+        "source": alert.target,
+        "severity": severity(alert.severity),
+        "class": alert.alert_type.to_string(),
+        "custom_details": details,
+    });
+    if !status.master_host.is_empty() {
+        payload["component"] = serde_json::json!(status.master_host);
+    }
+    if !alert.created_at.is_empty() {
+        payload["timestamp"] = serde_json::json!(alert.created_at);
+    }
+
+    Ok(serde_json::json!({
+        "routing_key": routing_key,
+        "event_action": "trigger",
+        "dedup_key": dedup_key(alert),
+        "payload": payload,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::alerts::routing::Channel;
+    use hyper::service::{make_service_fn, service_fn};
+    use hyper::{Request, Response, Server, StatusCode};
+    use std::sync::{Arc, Mutex};
+
+    /// A local stand-in for the Events API, answering `202 Accepted` and
+    /// keeping the events it receives.
+    fn mock_events_api() -> (String, Arc<Mutex<Vec<serde_json::Value>>>) {
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let received = events.clone();
+        let make_service = make_service_fn(move |_| {
+            let events = received.clone();
+            async move {
+                Ok::<_, hyper::Error>(service_fn(move |request: Request<Body>| {
+                    let events = events.clone();
+                    async move {
+                        let body = hyper::body::to_bytes(request.into_body()).await?;
+                        events
+                            .lock()
+                            .unwrap()
+                            .push(serde_json::from_slice(&body).unwrap());
+                        let mut response = Response::new(Body::from(r#"{"status":"success"}"#));
+                        *response.status_mut() = StatusCode::ACCEPTED;
+                        Ok::<_, hyper::Error>(response)
+                    }
+                }))
+            }
+        });
+        let server = Server::try_bind(&([127, 0, 0, 1], 0).into())
+            .unwrap()
+            .serve(make_service);
+        let url = format!("http://{}/v2/enqueue", server.local_addr());
+        tokio::spawn(server);
+
+        (url, events)
+    }
+
+    #[tokio::test]
+    async fn test_sends_trigger_then_resolve_to_url() {
+        let (url, events) = mock_events_api();
+        let notifier = PagerDutyNotifier {
+            routing_key: String::from("key"),
+            url,
+        };
+        let route = Route::new(Channel::new("pagerduty"));
+
+        let mut alert: Alert<DBSlaveStatus> = Alert::default();
+        alert.target = String::from("db-1");
+        alert.name = String::from("replication_lag");
+        alert.severity = Severity::Critical;
+        notifier.notify(&alert, &route).await.unwrap();
+        alert.resolved = true;
+        notifier.notify(&alert, &route).await.unwrap();
+
+        let events = events.lock().unwrap();
+        assert_eq!(2, events.len());
+        assert_eq!("trigger", events[0]["event_action"]);
+        assert_eq!("resolve", events[1]["event_action"]);
+        assert_eq!("key", events[1]["routing_key"]);
+        assert_eq!("sentinel/db-1/replication_lag", events[0]["dedup_key"]);
+        assert_eq!(events[0]["dedup_key"], events[1]["dedup_key"]);
+    }
+
+    #[test]
+    fn test_trigger_and_resolve_share_dedup_key() {
+        let mut alert: Alert<DBSlaveStatus> = Alert::default();
+        alert.target = String::from("db-1");
+        alert.name = String::from("replication_lag");
+        alert.severity = Severity::Critical;
+
+        let trigger = event("key", &alert).unwrap();
+        assert_eq!("trigger", trigger["event_action"]);
+        assert_eq!("critical", trigger["payload"]["severity"]);
+        assert_eq!("db-1", trigger["payload"]["source"]);
+        assert!(trigger["payload"]["custom_details"]["slave_io_running"].is_string());
+
+        alert.resolved = true;
+        let resolve = event("key", &alert).unwrap();
+        assert_eq!("resolve", resolve["event_action"]);
+        assert_eq!("sentinel/db-1/replication_lag", resolve["dedup_key"]);
+        assert_eq!(trigger["dedup_key"], resolve["dedup_key"]);
+    }
+}
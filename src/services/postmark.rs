@@ -1,58 +1,142 @@
-use crate::configure;
-use hyper::{Body, Client, Method, Request, Response};
-use hyper_tls::HttpsConnector;
+use crate::alerts::routing::Route;
+use crate::dbslave::DBSlaveStatus;
+use crate::errors::Error;
+use crate::monitor::Alert;
+use crate::services::notifier::{self, Notifier};
+use crate::utils;
+use crate::utils::json_request::{self, escape};
+use async_trait::async_trait;
+use hyper::{Body, Method, Response};
 use std::collections::HashMap;
 
+/// Emails alerts through Postmark. Each setting of the channel falls back to
+/// the top-level `postmark_*` flag.
+pub struct PostmarkNotifier {
+    server_token: String,
+    from: String,
+    reply_to: String,
+    to: String,
+}
+
+impl PostmarkNotifier {
+    pub fn from_config(settings: &config::Value) -> Result<Box<dyn Notifier>, Error> {
+        Ok(Box::new(PostmarkNotifier {
+            server_token: notifier::setting(settings, "server_token", "postmark_server_token")?,
+            from: notifier::setting(settings, "from", "postmark_from")?,
+            reply_to: notifier::setting(settings, "reply_to", "postmark_replyto")?,
+            to: notifier::setting(settings, "to", "postmark_to")?,
+        }))
+    }
+}
+
+/// The subject line of an alert email.
+pub fn subject(alert: &Alert<DBSlaveStatus>) -> String {
+    let beijing_timestamp = utils::time::get_beijing_timestamp_as_rfc2822();
+    let kind = if alert.resolved { "Resolved" } else { "Alert" };
+
+    format!(
+        "Sentinel Monitoring {} [{}] ({}: {}): DB Slave {} @ {} (Beijing)",
+        kind,
+        alert.severity.to_string().to_uppercase(),
+        alert.describe(|alert| alert.alert_type.to_string()),
+        alert.name,
+        alert.target,
+        beijing_timestamp
+    )
+}
+
+#[async_trait]
+impl Notifier for PostmarkNotifier {
+    async fn notify(&self, alert: &Alert<DBSlaveStatus>, route: &Route) -> Result<(), Error> {
+        let to = route.to.as_deref().unwrap_or(&self.to);
+
+        let mut postmark_template = String::new();
+        postmark_template.push_str(
+            r#"
+  {
+    "From": ""#,
+        );
+        postmark_template.push_str(&escape(&self.from));
+        postmark_template.push_str(
+            r#"",
+    "ReplyTo": ""#,
+        );
+        postmark_template.push_str(&escape(&self.reply_to));
+        postmark_template.push_str(
+            r#"",
+    "To": ""#,
+        );
+        postmark_template.push_str(&escape(to));
+        postmark_template.push_str(
+            r#"",
+    "Subject": ""#,
+        );
+        postmark_template.push_str(&escape(&subject(alert)));
+        postmark_template.push_str(
+            r#"",
+    "TextBody": ""#,
+        );
+        postmark_template.push_str(&escape(alert.message()));
+        postmark_template.push_str(
+            r#""
+  }"#,
+        );
+
+        let data: serde_json::Value = serde_json::from_str(&postmark_template)?;
+        let (response, response_value) = notify(&self.server_token, &data).await?;
+
+        info!(
+            "Postmark Response Status: {}, Postmark Response: {:#?}",
+            response.status(),
+            response_value
+        );
+
+        Ok(())
+    }
+}
+
 async fn post(
     url: &str,
+    postmark_server_token: &str,
     payload: Body,
 ) -> Result<(Response<Body>, hyper::body::Bytes), Box<dyn std::error::Error + Send + Sync>> {
-    let postmark_server_token: String =
-        configure::fetch::<String>(String::from("postmark_server_token")).unwrap();
-    let req = Request::builder()
-        .method(Method::POST)
-        .uri(url)
-        .header("content-type", "application/json")
-        .header("X-Postmark-Server-Token", postmark_server_token)
-        .body(payload)?;
-
-    let https = HttpsConnector::new();
-    let client = Client::builder().build::<_, Body>(https);
-    let mut response = client.request(req).await.unwrap();
-
-    let body = hyper::body::to_bytes(response.body_mut()).await.unwrap();
-
-    Ok((response, body))
+    let headers = [(
+        String::from("X-Postmark-Server-Token"),
+        String::from(postmark_server_token),
+    )];
+
+    json_request::send(Method::POST, url, &headers, payload).await
 }
 
 pub async fn notify(
+    postmark_server_token: &str,
     data: &serde_json::Value,
-) -> Result<(Response<Body>, serde_json::Value), Box<dyn std::error::Error + Send + Sync>> {
+) -> Result<(Response<Body>, serde_json::Value), Error> {
     let url = "https://api.postmarkapp.com/email";
     let payload = Body::from(data.to_string());
 
-    let (response, body): (Response<Body>, hyper::body::Bytes) = match post(&url, payload).await {
-        Ok(result) => result,
-        Err(error) => panic!("Error [postmark]: {:#?}", error),
-    };
+    let (response, body): (Response<Body>, hyper::body::Bytes) =
+        post(url, postmark_server_token, payload)
+            .await
+            .map_err(|error| Error::Internal(format!("Postmark: {}", error)))?;
 
     let body_string = String::from_utf8_lossy(&body);
-    let json_value: serde_json::Value = match serde_json::from_str(&body_string) {
-        Ok(value) => value,
-        Err(error) => panic!("Err: parsing JSON {:#?} / body: {:#?}", error, body_string),
-    };
-
-    // Patch error from Postmark
-    // "No Account or Server API tokens were supplied in the HTTP headers.
-    // Please add a header for either X-Postmark-Server-Token or
-    // X-Postmark-Account-Token."
-    if response.status().eq(&422) && 10.eq(&json_value["ErrorCode"]) {
-        panic!("Postmark Error: {:#?}, {}:{}", json_value, file!(), line!());
-    }
+    let json_value: serde_json::Value = serde_json::from_str(&body_string).map_err(|error| {
+        Error::Internal(format!(
+            "Postmark: parsing JSON {} / body: {}",
+            error, body_string
+        ))
+    })?;
 
-    // Patch error from Postmark
-    if response.status().eq(&422) && 300.eq(&json_value["ErrorCode"]) {
-        panic!("Postmark Error: {:#?}, {}:{}", json_value, file!(), line!());
+    // Errors from Postmark, e.g. 422 with `ErrorCode` 10, "No Account or
+    // Server API tokens were supplied in the HTTP headers", or 300, an
+    // invalid email request.
+    if !response.status().is_success() {
+        return Err(Error::Internal(format!(
+            "Postmark answered {}: {}",
+            response.status(),
+            json_value
+        )));
     }
 
     // Patch for success from Slack
@@ -1,20 +1,53 @@
-use crate::configure;
+use crate::alerts::routing::Route;
+use crate::dbslave::DBSlaveStatus;
 use crate::errors;
+use crate::monitor::Alert;
+use crate::services::notifier::{self, Notifier};
+use async_trait::async_trait;
 use hyper::{Body, Response};
 
 use crate::utils::json_request;
 
+/// Posts alerts to a Slack incoming webhook, `url` of the channel or
+/// `slack_url`.
+pub struct SlackNotifier {
+    url: String,
+}
+
+impl SlackNotifier {
+    pub fn from_config(settings: &config::Value) -> Result<Box<dyn Notifier>, errors::Error> {
+        Ok(Box::new(SlackNotifier {
+            url: notifier::setting(settings, "url", "slack_url")?,
+        }))
+    }
+}
+
+#[async_trait]
+impl Notifier for SlackNotifier {
+    async fn notify(
+        &self,
+        alert: &Alert<DBSlaveStatus>,
+        _route: &Route,
+    ) -> Result<(), errors::Error> {
+        let data: serde_json::Value = serde_json::from_str(alert.template())?;
+        let (_, body_json) = notify(&self.url, &data).await?;
+
+        info!("Slack response: {:#?}", body_json);
+
+        Ok(())
+    }
+}
+
 pub async fn notify(
+    slack_url: &str,
     data: &serde_json::Value,
-) -> Result<(Response<Body>, serde_json::Value), Box<dyn std::error::Error + Send + Sync>> {
-    let slack_url: String = configure::fetch::<String>(String::from("slack_url")).unwrap();
+) -> Result<(Response<Body>, serde_json::Value), errors::Error> {
     let payload = Body::from(data.to_string());
 
     let (response, body): (Response<Body>, hyper::body::Bytes) =
-        match json_request::post(&slack_url, payload).await {
-            Ok(result) => result,
-            Err(error) => panic!("Error [slack]: {:#?}", error),
-        };
+        json_request::post(slack_url, payload)
+            .await
+            .map_err(|error| errors::Error::Internal(format!("Slack: {}", error)))?;
 
     let body_string = String::from_utf8_lossy(&body);
 
@@ -24,26 +57,22 @@ pub async fn notify(
         return Ok((response, new_string));
     }
 
-    // Handle invalid token error
-    if response.status().eq(&403) && body_string.eq("invalid_token") {
-        panic!(
-            "Invalid Slack token used! Err: {:#?}",
-            errors::Error::InvalidTokenError
-        );
-    }
-
-    // Handle invalid team error
-    if response.status().eq(&404) && body_string.eq("no_team") {
-        panic!(
-            "Invalid team specified within token! Err: {:#?}",
-            errors::Error::InvalidTokenError
-        );
+    // Errors come back as plain text, e.g. 403 `invalid_token`, 404
+    // `no_team` or 400 `invalid_payload`.
+    if !response.status().is_success() {
+        return Err(errors::Error::Internal(format!(
+            "Slack answered {}: {}",
+            response.status(),
+            body_string
+        )));
     }
 
-    let json_value = match serde_json::from_str(&body_string) {
-        Ok(value) => value,
-        Err(error) => panic!("Err: parsing JSON {:#?} / body: {:#?}", error, body_string),
-    };
+    let json_value = serde_json::from_str(&body_string).map_err(|error| {
+        errors::Error::Internal(format!(
+            "Slack: parsing JSON {} / body: {}",
+            error, body_string
+        ))
+    })?;
 
     Ok((response, json_value))
 }
@@ -0,0 +1,231 @@
+use crate::alerts::routing::Route;
+use crate::alerts::Severity;
+use crate::dbslave::DBSlaveStatus;
+use crate::errors::Error;
+use crate::monitor::Alert;
+use crate::services::notifier::Notifier;
+use crate::services::postmark;
+use async_trait::async_trait;
+use hyper::{Body, Response};
+use serde::Deserialize;
+
+use crate::utils::json_request;
+
+#[derive(Debug, Deserialize)]
+struct TeamsConfig {
+    url: String,
+}
+
+/// Posts alerts to a Microsoft Teams incoming webhook, `url` of the channel,
+/// as an Adaptive Card.
+pub struct TeamsNotifier {
+    url: String,
+}
+
+impl TeamsNotifier {
+    pub fn from_config(settings: &config::Value) -> Result<Box<dyn Notifier>, Error> {
+        let config = settings.clone().try_into::<TeamsConfig>()?;
+
+        Ok(Box::new(TeamsNotifier { url: config.url }))
+    }
+}
+
+#[async_trait]
+impl Notifier for TeamsNotifier {
+    async fn notify(&self, alert: &Alert<DBSlaveStatus>, _route: &Route) -> Result<(), Error> {
+        let data = card(alert);
+        let (response, body) = notify(&self.url, &data)
+            .await
+            .map_err(|error| Error::Internal(format!("Teams: {}", error)))?;
+
+        let body_string = String::from_utf8_lossy(&body);
+        if !response.status().is_success() {
+            return Err(Error::Internal(format!(
+                "Teams answered {}: {}",
+                response.status(),
+                body_string
+            )));
+        }
+
+        info!("Teams response: {}", body_string);
+
+        Ok(())
+    }
+}
+
+pub async fn notify(
+    teams_url: &str,
+    data: &serde_json::Value,
+) -> Result<(Response<Body>, hyper::body::Bytes), Box<dyn std::error::Error + Send + Sync>> {
+    let payload = Body::from(data.to_string());
+
+    json_request::post(teams_url, payload).await
+}
+
+fn color(alert: &Alert<DBSlaveStatus>) -> &'static str {
+    if alert.resolved {
+        return "Good";
+    }
+
+    match alert.severity {
+        Severity::Info => "Accent",
+        Severity::Warning => "Warning",
+        Severity::Critical => "Attention",
+    }
+}
+
+fn optional(value: Option<u64>) -> String {
+    value.map_or_else(|| String::from("unknown"), |value| value.to_string())
+}
+
+/// Every field of the replica status, labelled.
+fn status_facts(status: &DBSlaveStatus) -> Vec<(&'static str, String)> {
+    vec![
+        ("Server version", status.server_version.clone()),
+        ("Master host", status.master_host.clone()),
+        ("Master user", status.master_user.clone()),
+        ("Slave IO running", status.slave_io_running.clone()),
+        ("Slave SQL running", status.slave_sql_running.clone()),
+        (
+            "Seconds behind master",
+            optional(status.seconds_behind_master),
+        ),
+        ("Heartbeat lag", optional(status.heartbeat_lag)),
+        ("Replay lag bytes", optional(status.replay_lag_bytes)),
+        ("Receive LSN", status.receive_lsn.clone()),
+        ("Replay LSN", status.replay_lsn.clone()),
+        ("Master log file", status.master_log_file.clone()),
+        (
+            "Read master log pos",
+            status.read_master_log_pos.to_string(),
+        ),
+        ("Relay log file", status.relay_log_file.clone()),
+        ("Relay log pos", status.relay_log_pos.to_string()),
+        (
+            "Relay master log file",
+            status.relay_master_log_file.clone(),
+        ),
+        ("Last IO errno", status.last_io_errno.to_string()),
+        ("Last IO error", status.last_io_error.clone()),
+        (
+            "Last IO error timestamp",
+            status.last_io_error_timestamp.clone(),
+        ),
+        ("Last SQL errno", status.last_sql_errno.to_string()),
+        ("Last SQL error", status.last_sql_error.clone()),
+        (
+            "Last SQL error timestamp",
+            status.last_sql_error_timestamp.clone(),
+        ),
+        ("Retrieved GTID set", status.retrieved_gtid_set.clone()),
+        ("Executed GTID set", status.executed_gtid_set.clone()),
+        ("Auto position", status.auto_position.to_string()),
+    ]
+}
+
+fn fact_set(facts: Vec<(&str, String)>) -> serde_json::Value {
+    let facts: Vec<serde_json::Value> = facts
+        .into_iter()
+        .map(|(title, value)| {
+            let value = if value.is_empty() {
+                String::from("-")
+            } else {
+                value
+            };
+            serde_json::json!({ "title": title, "value": value })
+        })
+        .collect();
+
+    serde_json::json!({ "type": "FactSet", "facts": facts })
+}
+
+/// The Adaptive Card of an alert, with the status of each replica it covers.
+fn card(alert: &Alert<DBSlaveStatus>) -> serde_json::Value {
+    // The Slack `*bold*` of the message would read as italics in Teams.
+    let message = alert.message().trim().replace('*', "**");
+
+    let mut body = vec![
+        serde_json::json!({
+            "type": "TextBlock",
+            "text": postmark::subject(alert),
+            "weight": "Bolder",
+            "size": "Medium",
+            "color": color(alert),
+            "wrap": true,
+        }),
+        fact_set(vec![
+            ("Severity", alert.severity.to_string()),
+            (
+                "Status",
+                String::from(if alert.resolved { "Resolved" } else { "Firing" }),
+            ),
+            (
+                "Alert",
+                format!(
+                    "{} ({})",
+                    alert.name,
+                    alert.describe(|alert| alert.alert_type.to_string())
+                ),
+            ),
+            ("Replica", alert.target.clone()),
+            ("Raised at (UTC)", alert.created_at.clone()),
+        ]),
+        serde_json::json!({ "type": "TextBlock", "text": message, "wrap": true }),
+    ];
+
+    for alert in alert.alerts() {
+        body.push(serde_json::json!({
+            "type": "TextBlock",
+            "text": format!("Replica {}", alert.target),
+            "weight": "Bolder",
+            "separator": true,
+        }));
+        body.push(fact_set(status_facts(alert.data())));
+    }
+
+    serde_json::json!({
+        "type": "message",
+        "attachments": [{
+            "contentType": "application/vnd.microsoft.card.adaptive",
+            "contentUrl": null,
+            "content": {
+                "$schema": "http://adaptivecards.io/schemas/adaptive-card.json",
+                "type": "AdaptiveCard",
+                "version": "1.4",
+                "msteams": { "width": "Full" },
+                "body": body,
+            },
+        }],
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_card_covers_every_status_field() {
+        let status = DBSlaveStatus::default();
+        let fields = match serde_json::to_value(&status).unwrap() {
+            serde_json::Value::Object(fields) => fields.len(),
+            _ => 0,
+        };
+        assert_eq!(fields, status_facts(&status).len());
+
+        let mut alert: Alert<DBSlaveStatus> = Alert::default();
+        alert.target = String::from("db-1");
+        alert.severity = Severity::Critical;
+        let data = card(&alert);
+        let content = &data["attachments"][0]["content"];
+        assert_eq!("AdaptiveCard", content["type"]);
+        assert_eq!("Attention", content["body"][0]["color"]);
+        let replica = content["body"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .rev()
+            .find(|element| element["type"] == "FactSet")
+            .unwrap();
+        assert_eq!(fields, replica["facts"].as_array().unwrap().len());
+    }
+}
@@ -0,0 +1,210 @@
+use crate::alerts::routing::Route;
+use crate::dbslave::DBSlaveStatus;
+use crate::errors::Error;
+use crate::monitor::Alert;
+use crate::services::notifier::Notifier;
+use crate::services::postmark;
+use crate::utils::json_request::{self, escape};
+use async_trait::async_trait;
+use hyper::{Body, Method};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+
+/// The body sent when a channel has no `body` template.
+const DEFAULT_BODY: &str = r#"{
+  "target": "{{target}}",
+  "rule": "{{rule}}",
+  "alert_type": "{{alert_type}}",
+  "severity": "{{severity}}",
+  "status": "{{status}}",
+  "subject": "{{subject}}",
+  "message": "{{message}}",
+  "to": "{{to}}",
+  "created_at": "{{created_at}}"
+}"#;
+
+/// Placeholders a body template can use. Each is replaced by text escaped for
+/// a JSON string, so it belongs between quotes.
+const PLACEHOLDERS: &[&str] = &[
+    "target",
+    "rule",
+    "alert_type",
+    "severity",
+    "status",
+    "subject",
+    "message",
+    "to",
+    "created_at",
+    "master_host",
+];
+
+fn default_method() -> String {
+    String::from("POST")
+}
+
+#[derive(Debug, Deserialize)]
+struct WebhookConfig {
+    url: String,
+    #[serde(default = "default_method")]
+    method: String,
+    #[serde(default)]
+    headers: BTreeMap<String, String>,
+    body: Option<String>,
+    expected_status: Option<u16>,
+}
+
+/// Sends alerts as JSON to any HTTP receiver, with the channel's `method`,
+/// `headers` and `body` template. The response must have `expected_status`,
+/// or any 2xx status when none is set.
+#[derive(Debug)]
+pub struct WebhookNotifier {
+    url: String,
+    method: Method,
+    headers: Vec<(String, String)>,
+    body: String,
+    expected_status: Option<u16>,
+}
+
+impl WebhookNotifier {
+    pub fn from_config(settings: &config::Value) -> Result<Box<dyn Notifier>, Error> {
+        let config = settings.clone().try_into::<WebhookConfig>()?;
+        let method = Method::from_bytes(config.method.to_uppercase().as_bytes())
+            .map_err(|_| Error::Internal(format!("Invalid webhook method: {}", config.method)))?;
+        let body = config.body.unwrap_or_else(|| String::from(DEFAULT_BODY));
+        check_template(&body)?;
+
+        Ok(Box::new(WebhookNotifier {
+            url: config.url,
+            method,
+            headers: config.headers.into_iter().collect(),
+            body,
+            expected_status: config.expected_status,
+        }))
+    }
+
+    fn accepts(&self, status: hyper::StatusCode) -> bool {
+        match self.expected_status {
+            Some(expected) => status.as_u16() == expected,
+            None => status.is_success(),
+        }
+    }
+}
+
+#[async_trait]
+impl Notifier for WebhookNotifier {
+    async fn notify(&self, alert: &Alert<DBSlaveStatus>, route: &Route) -> Result<(), Error> {
+        let status = if alert.resolved { "resolved" } else { "firing" };
+        // Each value is escaped for embedding in the JSON body.
+        let values = [
+            ("target", escape(&alert.target)),
+            ("rule", escape(&alert.name)),
+            (
+                "alert_type",
+                escape(&alert.describe(|alert| alert.alert_type.to_string())),
+            ),
+            ("severity", escape(&alert.severity.to_string())),
+            ("status", String::from(status)),
+            ("subject", escape(&postmark::subject(alert))),
+            ("message", escape(alert.message())),
+            ("to", escape(route.to.as_deref().unwrap_or_default())),
+            ("created_at", escape(&alert.created_at)),
+            (
+                "master_host",
+                escape(&alert.describe(|alert| alert.data().master_host.clone())),
+            ),
+        ];
+
+        let data: serde_json::Value = serde_json::from_str(&render(&self.body, &values)?)?;
+        let (response, body) = json_request::send(
+            self.method.clone(),
+            &self.url,
+            &self.headers,
+            Body::from(data.to_string()),
+        )
+        .await
+        .map_err(|error| Error::Internal(format!("Webhook {}: {}", self.url, error)))?;
+
+        let body_string = String::from_utf8_lossy(&body);
+        if !self.accepts(response.status()) {
+            return Err(Error::Internal(format!(
+                "Webhook {} answered {}: {}",
+                self.url,
+                response.status(),
+                body_string
+            )));
+        }
+
+        info!(
+            "Webhook Response Status: {}, Webhook Response: {}",
+            response.status(),
+            body_string
+        );
+
+        Ok(())
+    }
+}
+
+/// Replace each `{{name}}` in `template` by its value, in one pass so that
+/// values are never read as placeholders.
+fn render(template: &str, values: &[(&str, String)]) -> Result<String, Error> {
+    let mut body = String::new();
+    let mut rest = template;
+    while let Some(start) = rest.find("{{") {
+        body.push_str(&rest[..start]);
+        let after = &rest[start + 2..];
+        let end = after
+            .find("}}")
+            .ok_or_else(|| Error::Internal(String::from("Unclosed placeholder in webhook body")))?;
+        let name = &after[..end];
+        let value = values
+            .iter()
+            .find(|(key, _)| *key == name)
+            .map(|(_, value)| value)
+            .ok_or_else(|| {
+                Error::Internal(format!(
+                    "Unknown placeholder in webhook body: {{{{{}}}}}",
+                    name
+                ))
+            })?;
+        body.push_str(value);
+        rest = &after[end + 2..];
+    }
+    body.push_str(rest);
+
+    Ok(body)
+}
+
+/// Check that `template` only uses known placeholders and renders to JSON.
+fn check_template(template: &str) -> Result<(), Error> {
+    let values: Vec<(&str, String)> = PLACEHOLDERS
+        .iter()
+        .map(|name| (*name, String::new()))
+        .collect();
+    serde_json::from_str::<serde_json::Value>(&render(template, &values)?)?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_body_template() {
+        assert!(check_template(DEFAULT_BODY).is_ok());
+        assert!(check_template(r#"{"text": "{{target}} {{lag}}"}"#).is_err());
+        assert!(check_template(r#"{"text": "{{target"}"#).is_err());
+        assert!(check_template(r#"{"text": {{target}}}"#).is_err());
+
+        let body = render(
+            r#"{"text": "{{target}}: {{rule}}"}"#,
+            &[
+                ("target", escape("db {{rule}}")),
+                ("rule", String::from("lag")),
+            ],
+        )
+        .unwrap();
+        let data: serde_json::Value = serde_json::from_str(&body).unwrap();
+        assert_eq!("db {{rule}}: lag", data["text"]);
+    }
+}
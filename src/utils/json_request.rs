@@ -2,6 +2,14 @@ use crate::errors::Error;
 use hyper::{Body, Client, Method, Request, Response};
 use hyper_tls::HttpsConnector;
 
+/// Escape `text` for embedding within a string literal of a hand-built JSON
+/// template, without the surrounding quotes.
+pub fn escape(text: &str) -> String {
+    let quoted = serde_json::Value::from(text).to_string();
+
+    String::from(&quoted[1..quoted.len() - 1])
+}
+
 pub async fn get(url: &'static str) -> Result<serde_json::Value, Error> {
     let uri = hyper::Uri::from_static(url);
     let https = HttpsConnector::new();
@@ -21,17 +29,34 @@ pub async fn post(
     url: &str,
     payload: Body,
 ) -> Result<(Response<Body>, hyper::body::Bytes), Box<dyn std::error::Error + Send + Sync>> {
-    let req = Request::builder()
-        .method(Method::POST)
-        .uri(url)
-        .header("content-type", "application/json")
-        .body(payload)?;
+    send(Method::POST, url, &[], payload).await
+}
+
+/// Send a JSON `payload` with `method`, adding `headers` to the request. A
+/// `content-type` among `headers` replaces the JSON one.
+pub async fn send(
+    method: Method,
+    url: &str,
+    headers: &[(String, String)],
+    payload: Body,
+) -> Result<(Response<Body>, hyper::body::Bytes), Box<dyn std::error::Error + Send + Sync>> {
+    let mut builder = Request::builder().method(method).uri(url);
+    if !headers
+        .iter()
+        .any(|(name, _)| name.eq_ignore_ascii_case("content-type"))
+    {
+        builder = builder.header("content-type", "application/json");
+    }
+    for (name, value) in headers {
+        builder = builder.header(name.as_str(), value.as_str());
+    }
+    let req = builder.body(payload)?;
 
     let https = HttpsConnector::new();
     let client = Client::builder().build::<_, Body>(https);
-    let mut response = client.request(req).await.unwrap();
+    let mut response = client.request(req).await?;
 
-    let body = hyper::body::to_bytes(response.body_mut()).await.unwrap();
+    let body = hyper::body::to_bytes(response.body_mut()).await?;
 
     Ok((response, body))
 }
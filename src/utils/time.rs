@@ -29,7 +29,7 @@ pub fn to_rfc_rfc3339(naive_dt: chrono::NaiveDateTime) -> Result<DateTime<FixedO
     let naive_string = naive_dt.to_string();
     let re = Regex::new(r"[\s]+").unwrap();
     let mut corrected = String::from(re.replace(&naive_string[..], "T"));
-    corrected.push_str("Z");
+    corrected.push('Z');
 
     let dt = match DateTime::parse_from_rfc3339(&corrected[..]) {
         Ok(value) => value,
@@ -72,7 +72,7 @@ pub fn get_utc_timestamp_as_rfc2822() -> String {
 }
 
 pub fn get_beijing_timestamp_as_rfc2822() -> String {
-    let beijing_timezone = FixedOffset::east(8 * 3600);
+    let beijing_timezone = FixedOffset::east_opt(8 * 3600).unwrap();
 
     get_utc_time().with_timezone(&beijing_timezone).to_rfc2822()
 }
@@ -81,6 +81,20 @@ pub fn timestamp_as_rfc2822_from_utc(utc: DateTime<Utc>) -> String {
     utc.to_rfc2822()
 }
 
+/// Format a duration for notifications, e.g. `1h 02m 03s`.
+pub fn format_duration(duration: Duration) -> String {
+    let seconds = duration.num_seconds().max(0);
+    let (hours, minutes, seconds) = (seconds / 3600, seconds % 3600 / 60, seconds % 60);
+
+    if hours > 0 {
+        format!("{}h {:02}m {:02}s", hours, minutes, seconds)
+    } else if minutes > 0 {
+        format!("{}m {:02}s", minutes, seconds)
+    } else {
+        format!("{}s", seconds)
+    }
+}
+
 #[allow(dead_code)]
 fn time_since_epoch_in_millis() -> u128 {
     let start = SystemTime::now();
@@ -100,7 +114,7 @@ mod tests {
         let previous = from_rfc_rfc3339("1996-12-19T16:39:57-08:00").unwrap();
         let current = from_rfc_rfc3339("2018-12-19T16:39:57-08:00").unwrap();
 
-        assert_eq!(true, is_greater(current, previous));
+        assert!(is_greater(current, previous));
     }
 
     #[test]
@@ -111,6 +125,13 @@ mod tests {
         let ts = Utc::now() - Duration::minutes(mins - 1);
         let timestamp = from_rfc_rfc3339(&ts.to_rfc3339()).unwrap();
 
-        assert_eq!(false, occurred_more_than_mins_ago(timestamp, now, mins));
+        assert!(!occurred_more_than_mins_ago(timestamp, now, mins));
+    }
+
+    #[test]
+    fn test_format_duration() {
+        assert_eq!("42s", format_duration(Duration::seconds(42)));
+        assert_eq!("5m 03s", format_duration(Duration::seconds(303)));
+        assert_eq!("1h 02m 03s", format_duration(Duration::seconds(3723)));
     }
 }